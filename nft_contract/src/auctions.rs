@@ -0,0 +1,324 @@
+// --- English Auctions ---
+// A seller locks a lot of copies into escrow on the contract account and bidders raise
+// each other by at least the auction's minimum increment, escrowing their bid in NEAR.
+// An outbid bidder's NEAR is credited to their pending withdrawals. A bid close to the
+// end pushes the end back, so nobody can snipe the lot in the last block. Once the
+// auction has ended anyone can settle it. Each bid also escrows enough NEAR for the storage
+// settling could need, which pays for the winner's entries and goes back to outbid bidders.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::store::IterableSet;
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+// A bid within this window of the end extends the auction to this long after the bid
+pub const AUCTION_EXTENSION_NS: u64 = 10 * 60 * 1_000_000_000;
+pub const MIN_AUCTION_DURATION_NS: u64 = 60 * 60 * 1_000_000_000;
+pub const MAX_AUCTION_DURATION_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+// Most bytes settling stores for the winner's copies, besides twice the class ID's length
+pub const AUCTION_HOLDING_STORAGE_BYTES: u64 = 500;
+// Most bytes settling stores for each payout recipient's pending withdrawal
+pub const AUCTION_PAYOUT_STORAGE_BYTES: u64 = 200;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bid {
+    pub bidder_id: AccountId,
+    pub amount: U128, // Total for the whole lot
+    pub storage_reserve: U128, // Escrowed for the storage settling needs, on top of the amount
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    pub auction_id: U64,
+    pub seller_id: AccountId,
+    pub token_class_id: String,
+    pub quantity: U64, // Copies in escrow, sold together as one lot
+    pub reserve_price: U128, // Lowest acceptable first bid
+    pub min_increment: U128, // Each bid must beat the previous one by at least this
+    pub ends_at: U64, // Block timestamp in nanoseconds
+    pub highest_bid: Option<Bid>,
+}
+
+impl Auction {
+    pub fn has_ended(&self) -> bool {
+        env::block_timestamp() >= self.ends_at.0
+    }
+
+    // Smallest bid the auction accepts right now
+    pub fn min_next_bid(&self) -> u128 {
+        match &self.highest_bid {
+            Some(bid) => bid.amount.0 + self.min_increment.0,
+            None => self.reserve_price.0,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: event data describing the current state of an auction
+    fn internal_auction_log(&self, auction: &Auction) -> Vec<AuctionLog> {
+        vec![AuctionLog {
+            auction_id: auction.auction_id,
+            seller_id: auction.seller_id.clone(),
+            token_class_id: auction.token_class_id.clone(),
+            amount: U128(u128::from(auction.quantity.0)),
+            bidder_id: auction.highest_bid.as_ref().map(|bid| bid.bidder_id.clone()),
+            bid: auction.highest_bid.as_ref().map(|bid| bid.amount).unwrap_or(auction.reserve_price),
+            ends_at: auction.ends_at,
+        }]
+    }
+
+    // Internal helper: the most settling an auction of this class can cost in storage, in yoctoNEAR.
+    // Covers a registration for the winner, their copies and a pending withdrawal per split holder and the seller.
+    fn internal_auction_storage_reserve(&self, token_class_id: &str) -> u128 {
+        let recipients = self.internal_split_sheet(token_class_id).len() as u64 + 1;
+        let bytes = self.account_storage_usage
+            + AUCTION_HOLDING_STORAGE_BYTES
+            + 2 * token_class_id.len() as u64
+            + recipients * AUCTION_PAYOUT_STORAGE_BYTES;
+        u128::from(bytes) * env::storage_byte_cost().as_yoctonear()
+    }
+
+    // Internal helper: removes an auction and drops it from the class index
+    fn internal_remove_auction(&mut self, auction_id: u64) -> Auction {
+        let auction = self.auctions.remove(&auction_id).expect("Auction not found");
+
+        if let Some(auction_ids) = self.auctions_by_class.get_mut(&auction.token_class_id) {
+            auction_ids.remove(&auction_id);
+            auction_ids.flush();
+            if auction_ids.is_empty() {
+                self.auctions_by_class.remove(&auction.token_class_id);
+            }
+        }
+        auction
+    }
+
+    /// Starts an auction for `quantity` copies held by the predecessor, moving them into escrow.
+    /// Bids are for the whole lot; the first must reach `reserve_price` and each later one must beat
+    /// the highest by `min_increment`. `duration` is in nanoseconds. Returns the auction ID.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn auction_create(
+        &mut self,
+        token_class_id: String,
+        quantity: U64,
+        reserve_price: U128,
+        min_increment: U128,
+        duration: U64
+    ) -> U64 {
+        let seller_id = env::predecessor_account_id();
+        assert!(quantity.0 > 0, "Auction quantity must be positive");
+        assert!(min_increment.0 > 0, "Minimum bid increment must be positive");
+        assert!(
+            (MIN_AUCTION_DURATION_NS..=MAX_AUCTION_DURATION_NS).contains(&duration.0),
+            "Auction duration must be between {} and {} nanoseconds", MIN_AUCTION_DURATION_NS, MAX_AUCTION_DURATION_NS
+        );
+        let initial_storage_usage = env::storage_usage();
+
+        // --- Escrow Copies ---
+        let escrow_id = env::current_account_id();
+        self.internal_transfer(&seller_id, &escrow_id, &token_class_id, quantity.0, &seller_id);
+
+        let auction_id = self.next_auction_id;
+        self.next_auction_id += 1;
+        let auction = Auction {
+            auction_id: U64(auction_id),
+            seller_id: seller_id.clone(),
+            token_class_id: token_class_id.clone(),
+            quantity,
+            reserve_price,
+            min_increment,
+            ends_at: U64(env::block_timestamp() + duration.0),
+            highest_bid: None,
+        };
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: None,
+            old_owner_id: seller_id,
+            new_owner_id: escrow_id,
+            token_ids: vec![token_class_id.clone()],
+            amounts: vec![U128(u128::from(quantity.0))],
+            memo: Some(format!("Auction {} escrow", auction_id)),
+        }]).emit();
+        MusicSftEvent::AuctionCreate(self.internal_auction_log(&auction)).emit();
+
+        let auction_ids = self.auctions_by_class
+            .entry(token_class_id.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::AuctionsByClassInner {
+                    token_class_hash: env::sha256_array(token_class_id.as_bytes()),
+                })
+            });
+        auction_ids.insert(auction_id);
+        auction_ids.flush();
+        self.auctions.insert(auction_id, auction);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        U64(auction_id)
+    }
+
+    /// Bids `bid` on an auction's lot, by default all of the attached deposit left after the storage reserve.
+    /// The deposit must cover the bid plus the reserve for the storage settling needs (see
+    /// `auction_storage_reserve`). The previous highest bidder's bid and reserve are credited to their
+    /// pending withdrawals. A bid in the last minutes extends the auction.
+    /// Storage is charged to the bidder's storage balance, topped up by the deposit attached beyond the bid
+    /// and reserve; a bidder who is not registered with `storage_deposit` must attach that extra.
+    #[payable]
+    pub fn auction_bid(&mut self, auction_id: U64, bid: Option<U128>) {
+        let bidder_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let token_class_id = self.auctions.get(&auction_id.0).expect("Auction not found").token_class_id.clone();
+        let storage_reserve = self.internal_auction_storage_reserve(&token_class_id);
+        let attached_deposit = env::attached_deposit().as_yoctonear();
+        let amount = bid.map(|bid| bid.0).unwrap_or(attached_deposit.saturating_sub(storage_reserve));
+        if attached_deposit < amount + storage_reserve {
+            env::panic_str(&format!(
+                "Insufficient deposit. Attached: {}, Required: {} for the bid and {} for settlement storage",
+                attached_deposit, amount, storage_reserve
+            ));
+        }
+        self.assert_released(&token_class_id);
+        self.assert_purchase_rules(&token_class_id, &bidder_id);
+        let auction = self.auctions.get_mut(&auction_id.0).expect("Auction not found");
+        assert!(!auction.has_ended(), "Auction has ended");
+        assert_ne!(bidder_id, auction.seller_id, "Seller cannot bid on their own auction");
+        let min_next_bid = auction.min_next_bid();
+        if amount < min_next_bid {
+            env::panic_str(&format!("Bid too low. Attached: {}, Required: {}", amount, min_next_bid));
+        }
+
+        // --- Anti-Sniping Extension ---
+        let now = env::block_timestamp();
+        if auction.ends_at.0 - now < AUCTION_EXTENSION_NS {
+            auction.ends_at = U64(now + AUCTION_EXTENSION_NS);
+        }
+
+        let outbid = auction.highest_bid.replace(Bid {
+            bidder_id: bidder_id.clone(),
+            amount: U128(amount),
+            storage_reserve: U128(storage_reserve),
+        });
+        let auction = auction.clone();
+        if let Some(outbid) = outbid {
+            let refund = outbid.amount.0 + outbid.storage_reserve.0;
+            self.internal_credit_all(&auction.token_class_id, vec![(outbid.bidder_id, refund)], Some(&bidder_id));
+        }
+        MusicSftEvent::AuctionBid(self.internal_auction_log(&auction)).emit();
+
+        self.internal_charge_storage(&bidder_id, initial_storage_usage, attached_deposit - amount - storage_reserve);
+    }
+
+    /// Ends an auction that has no bids and returns the copies to the seller.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn auction_cancel(&mut self, auction_id: U64) {
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let auction = self.auctions.get(&auction_id.0).expect("Auction not found");
+        assert_eq!(env::predecessor_account_id(), auction.seller_id, "Only the seller can cancel an auction");
+        assert!(auction.highest_bid.is_none(), "Auction already has bids");
+
+        let auction = self.internal_remove_auction(auction_id.0);
+        self.internal_release_auction_lot(&auction, &auction.seller_id, "Auction cancelled");
+        MusicSftEvent::AuctionCancel(self.internal_auction_log(&auction)).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Closes an auction once it has ended. The lot goes to the highest bidder and their bid pays
+    /// the platform fee, royalties and the seller; without bids the copies go back to the seller.
+    /// Anyone can call this. The seller gets back the storage they paid for the auction. The winner's
+    /// storage reserve goes to their storage balance and pays for the entries settling creates;
+    /// settling never fails on storage, any shortfall is left owed.
+    pub fn auction_settle(&mut self, auction_id: U64) {
+        let auction = self.auctions.get(&auction_id.0).expect("Auction not found");
+        assert!(auction.has_ended(), "Auction has not ended yet");
+        let initial_storage_usage = env::storage_usage();
+        let seller_id = auction.seller_id.clone();
+        let auction = self.internal_remove_paid_by(Some(&seller_id), |this| this.internal_remove_auction(auction_id.0));
+        let token_class_id = auction.token_class_id.clone();
+
+        let Some(bid) = auction.highest_bid.clone() else {
+            self.internal_release_auction_lot(&auction, &auction.seller_id, "Auction ended without bids");
+            MusicSftEvent::AuctionSettle(self.internal_auction_log(&auction)).emit();
+            self.internal_charge_storage_owed(&seller_id, initial_storage_usage, 0);
+            return;
+        };
+        self.internal_release_auction_lot(&auction, &bid.bidder_id, "Auction won");
+
+        // --- Collect Platform Fee, Pay Royalties & Seller ---
+        let total_price = bid.amount.0;
+        let market_fee = self.internal_collect_market_fee(&token_class_id, total_price, None);
+        let proceeds = total_price - market_fee;
+        let payout = self.internal_compute_payout(&token_class_id, &auction.seller_id, proceeds);
+        let royalty = self.internal_royalty_shares(&token_class_id, proceeds)
+            .iter()
+            .map(|(_, share)| share)
+            .sum::<u128>();
+        self.internal_credit_all(&token_class_id, payout.into_iter().collect(), Some(&bid.bidder_id));
+        self.internal_charge_storage_owed(&bid.bidder_id, initial_storage_usage, bid.storage_reserve.0);
+
+        MusicSftEvent::AuctionSettle(self.internal_auction_log(&auction)).emit();
+        MusicSftEvent::Sale(vec![SaleLog {
+            seller_id: auction.seller_id.clone(),
+            buyer_id: bid.bidder_id,
+            token_class_id,
+            amount: U128(u128::from(auction.quantity.0)),
+            price: U128(total_price),
+            royalty: Some(U128(royalty)),
+            market_fee: Some(U128(market_fee)),
+            ft_token_id: None,
+        }]).emit();
+    }
+
+    // Internal helper: moves an auction's escrowed copies out to `receiver_id`, who pays for their balance entry
+    fn internal_release_auction_lot(&mut self, auction: &Auction, receiver_id: &AccountId, memo: &str) {
+        let escrow_id = env::current_account_id();
+        self.internal_transfer(&escrow_id, receiver_id, &auction.token_class_id, auction.quantity.0, receiver_id);
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: None,
+            old_owner_id: escrow_id,
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![auction.token_class_id.clone()],
+            amounts: vec![U128(u128::from(auction.quantity.0))],
+            memo: Some(memo.to_string()),
+        }]).emit();
+    }
+
+    // --- Auction View functions ---
+
+    pub fn auction_get(&self, auction_id: U64) -> Option<Auction> {
+        self.auctions.get(&auction_id.0).cloned()
+    }
+
+    // Get the NEAR a bid on an auction must attach on top of the bid for settlement storage
+    pub fn auction_storage_reserve(&self, auction_id: U64) -> U128 {
+        let auction = self.auctions.get(&auction_id.0).expect("Auction not found");
+        U128(self.internal_auction_storage_reserve(&auction.token_class_id))
+    }
+
+    // Get the smallest bid an auction accepts right now
+    pub fn auction_min_next_bid(&self, auction_id: U64) -> U128 {
+        U128(self.auctions.get(&auction_id.0).expect("Auction not found").min_next_bid())
+    }
+
+    /// Returns a page of the auctions for a token class, ended but unsettled ones included.
+    pub fn auctions_for_class(&self, token_class_id: String, from_index: Option<u64>, limit: Option<u64>) -> Vec<Auction> {
+        let Some(auction_ids) = self.auctions_by_class.get(&token_class_id) else {
+            return Vec::new();
+        };
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(auction_ids.len() as u64);
+
+        auction_ids
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .filter_map(|auction_id| self.auctions.get(auction_id).cloned())
+            .collect()
+    }
+}
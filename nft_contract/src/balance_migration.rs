@@ -0,0 +1,328 @@
+// --- Balance Migration ---
+// Balances and approvals used to live in one map per owner, deserialized whole on every read or
+// transfer. They now sit in flat maps keyed by (owner, class), with a per-owner class index for
+// paging. A class moves out of an account's legacy maps when the account spends or approves copies
+// of it, or ahead of time in chunks by the contract owner with `migrate_legacy_accounts`. Copies
+// received meanwhile go straight to the flat maps and never migrate the receiver, so reads add up
+// both layouts until the account is done.
+
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+// Classes per account `migrate_legacy_accounts` moves when no limit is given
+pub const DEFAULT_MIGRATION_CHUNK: u32 = 100;
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: moves classes of an account's legacy balance and approval maps into the flat maps,
+    // only `token_class_id` when given or else up to `limit` of them. The legacy maps are read and written
+    // back once per call whatever their size. `storage_payer` pays for the new entries. Returns how many classes were moved.
+    pub(crate) fn internal_migrate_legacy_classes(
+        &mut self,
+        account_id: &AccountId,
+        token_class_id: Option<&str>,
+        limit: usize,
+        storage_payer: &AccountId,
+    ) -> u32 {
+        let token_class_ids: Vec<String> = {
+            let legacy_balances = self.legacy_owner_balances.get(account_id);
+            let legacy_approvals = self.legacy_approvals.get(account_id);
+            let is_legacy = |token_class_id: &str| {
+                legacy_balances.is_some_and(|balances| balances.contains_key(token_class_id))
+                    || legacy_approvals.is_some_and(|approvals| approvals.contains_key(token_class_id))
+            };
+            match token_class_id {
+                Some(token_class_id) if is_legacy(token_class_id) => vec![token_class_id.to_string()],
+                Some(_) => Vec::new(),
+                None => {
+                    let mut token_class_ids: Vec<String> = legacy_balances.iter().flat_map(|balances| balances.keys())
+                        .chain(legacy_approvals.iter().flat_map(|approvals| approvals.keys()))
+                        .cloned()
+                        .collect();
+                    token_class_ids.sort();
+                    token_class_ids.dedup();
+                    token_class_ids.truncate(limit);
+                    token_class_ids
+                }
+            }
+        };
+        if token_class_ids.is_empty() {
+            return 0;
+        }
+
+        let mut legacy_balances = self.legacy_owner_balances.remove(account_id).unwrap_or_default();
+        let mut legacy_approvals = self.legacy_approvals.remove(account_id).unwrap_or_default();
+        // Approvals of the contract itself came from the old approval-based market and are dropped
+        let marketplace_id = env::current_account_id();
+        for token_class_id in token_class_ids.iter() {
+            // Copies received since are already in the flat maps, so the legacy balance adds to them
+            if let Some(balance) = legacy_balances.remove(token_class_id).filter(|balance| *balance > 0) {
+                self.internal_deposit(account_id, token_class_id, balance, storage_payer);
+            }
+            for (approved_account_id, approved_amount) in legacy_approvals.remove(token_class_id).unwrap_or_default() {
+                if approved_amount > 0 && approved_account_id != marketplace_id {
+                    self.approvals.insert((account_id.clone(), token_class_id.clone(), approved_account_id), approved_amount);
+                }
+            }
+        }
+
+        if !legacy_balances.is_empty() {
+            self.legacy_owner_balances.insert(account_id.clone(), legacy_balances);
+        }
+        if !legacy_approvals.is_empty() {
+            self.legacy_approvals.insert(account_id.clone(), legacy_approvals);
+        }
+        if !self.is_legacy_account(account_id.clone()) {
+            self.legacy_owners.remove(account_id);
+        }
+        token_class_ids.len() as u32
+    }
+
+    /// Moves up to `limit` classes (default `DEFAULT_MIGRATION_CHUNK`) of each given account's legacy balances
+    /// and approvals into the flat maps. Large accounts take several calls; `is_legacy_account` tells when one is done.
+    /// Only the contract owner can call this; the storage change is settled against their storage balance.
+    /// Returns how many classes were moved.
+    #[payable]
+    pub fn migrate_legacy_accounts(&mut self, account_ids: Vec<AccountId>, limit: Option<u32>) -> u32 {
+        self.assert_owner();
+        let limit = limit.unwrap_or(DEFAULT_MIGRATION_CHUNK) as usize;
+        let owner_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        let mut migrated = 0;
+        for account_id in account_ids.iter() {
+            migrated += self.internal_migrate_legacy_classes(account_id, None, limit, &owner_id);
+        }
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        migrated
+    }
+
+    /// Returns a page of the accounts known to still have legacy balance maps, to feed `migrate_legacy_accounts`.
+    pub fn legacy_owners(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(self.legacy_owners.len() as u64);
+
+        self.legacy_owners
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // Get whether an account still keeps its balances in the legacy layout
+    pub fn is_legacy_account(&self, account_id: AccountId) -> bool {
+        self.legacy_owner_balances.contains_key(&account_id) || self.legacy_approvals.contains_key(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{account, set_context};
+    use near_sdk::json_types::U64;
+    use near_sdk::NearToken;
+    use std::collections::HashMap;
+
+    const FEW_CLASSES: u64 = 10;
+    const MANY_CLASSES: u64 = 1_000;
+    // More legacy classes than one call can move within the gas limit
+    const HUGE_LEGACY_CLASSES: u64 = 2_000;
+
+    fn class_id(index: u64) -> String {
+        format!("track-{:05}", index)
+    }
+
+    // Gives `holder_id` copies of `count` classes, spread over several calls to stay under the gas limit.
+    // Each call writes its cached changes out, as it would when the contract is dropped on chain.
+    fn fill_inventory(contract: &mut Contract, holder_id: &AccountId, count: u64) {
+        for index in 0..count {
+            if index % 100 == 0 {
+                contract.internal_flush();
+                set_context(holder_id, NearToken::from_yoctonear(0));
+            }
+            contract.internal_deposit(holder_id, &class_id(index), 10, holder_id);
+        }
+        contract.internal_flush();
+        set_context(holder_id, NearToken::from_near(10));
+        contract.storage_deposit(None, None);
+    }
+
+    // Gas burnt by `sft_transfer` of `amount` copies of the first class
+    fn transfer_gas(contract: &mut Contract, holder_id: &AccountId, receiver_id: &AccountId, amount: u64) -> u64 {
+        set_context(holder_id, NearToken::from_yoctonear(1));
+        let used_before = env::used_gas().as_gas();
+        contract.sft_transfer(receiver_id.clone(), class_id(0), U64(amount), None);
+        env::used_gas().as_gas() - used_before
+    }
+
+    fn assert_about_equal(small_inventory_gas: u64, large_inventory_gas: u64) {
+        assert!(
+            large_inventory_gas <= small_inventory_gas + small_inventory_gas / 100,
+            "Transfer gas grew with the inventory: {} vs {}", small_inventory_gas, large_inventory_gas
+        );
+    }
+
+    #[test]
+    fn transfer_gas_does_not_grow_with_inventory() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        fill_inventory(&mut contract, &account("holder-a.near"), FEW_CLASSES);
+        fill_inventory(&mut contract, &account("holder-b.near"), MANY_CLASSES);
+
+        let small = transfer_gas(&mut contract, &account("holder-a.near"), &account("receiver-a.near"), 1);
+        let large = transfer_gas(&mut contract, &account("holder-b.near"), &account("receiver-b.near"), 1);
+        assert_about_equal(small, large);
+        assert_eq!(contract.sft_balance_of(account("holder-b.near"), class_id(0)).0, 9);
+        assert_eq!(contract.sft_class_count_of_owner(account("holder-b.near")), MANY_CLASSES as u32);
+    }
+
+    #[test]
+    fn whole_balance_transfer_gas_does_not_grow_with_inventory() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        fill_inventory(&mut contract, &account("holder-a.near"), FEW_CLASSES);
+        fill_inventory(&mut contract, &account("holder-b.near"), MANY_CLASSES);
+
+        // Emptying a balance also drops the class from the holder's index
+        let small = transfer_gas(&mut contract, &account("holder-a.near"), &account("receiver-a.near"), 10);
+        let large = transfer_gas(&mut contract, &account("holder-b.near"), &account("receiver-b.near"), 10);
+        assert_about_equal(small, large);
+        assert_eq!(contract.sft_class_count_of_owner(account("holder-b.near")), MANY_CLASSES as u32 - 1);
+        assert_eq!(contract.sft_classes_of_owner(account("receiver-b.near"), None, None), vec![class_id(0)]);
+    }
+
+    #[test]
+    fn legacy_balance_reads_grow_with_inventory() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        fill_inventory(&mut contract, &account("holder-a.near"), MANY_CLASSES);
+        let legacy_balances: HashMap<String, u64> = (0..MANY_CLASSES).map(|index| (class_id(index), 10)).collect();
+        contract.legacy_owner_balances.insert(account("holder-b.near"), legacy_balances);
+        contract.legacy_owner_balances.flush();
+
+        let mut read_gas = |holder_id: &AccountId| {
+            set_context(holder_id, NearToken::from_yoctonear(0));
+            // Read from storage rather than from what the maps cached while being filled
+            contract.balances = LookupMap::new(StorageKey::Balances);
+            contract.legacy_owner_balances = LookupMap::new(StorageKey::OwnerBalances);
+            let used_before = env::used_gas().as_gas();
+            assert_eq!(contract.internal_balance_of(holder_id, &class_id(0)), 10);
+            env::used_gas().as_gas() - used_before
+        };
+        let flat = read_gas(&account("holder-a.near"));
+        let legacy = read_gas(&account("holder-b.near"));
+        assert!(legacy > flat * 2, "Legacy read should cost more: {} vs {}", legacy, flat);
+    }
+
+    #[test]
+    fn migrates_legacy_balances_and_approvals() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        let holder_id = account("holder.near");
+        let fan_id = account("fan.near");
+        let market_id = account("music.near");
+        contract.legacy_owner_balances.insert(
+            holder_id.clone(),
+            HashMap::from([(class_id(0), 5), (class_id(1), 2)]),
+        );
+        contract.legacy_approvals.insert(
+            holder_id.clone(),
+            HashMap::from([(class_id(0), HashMap::from([(fan_id.clone(), 3), (market_id.clone(), 4)]))]),
+        );
+
+        // Reads fall back to the legacy maps before migration
+        assert!(contract.is_legacy_account(holder_id.clone()));
+        assert_eq!(contract.sft_balance_of(holder_id.clone(), class_id(1)).0, 2);
+        assert_eq!(contract.sft_is_approved(holder_id.clone(), fan_id.clone(), class_id(0)).0, 3);
+        assert_eq!(contract.sft_is_approved(holder_id.clone(), market_id.clone(), class_id(0)).0, 0);
+        assert_eq!(contract.sft_classes_of_owner(holder_id.clone(), Some(1), Some(1)), vec![class_id(1)]);
+
+        set_context(&account("owner.near"), NearToken::from_near(1));
+        assert_eq!(contract.migrate_legacy_accounts(vec![holder_id.clone(), account("nobody.near")], None), 2);
+
+        assert!(!contract.is_legacy_account(holder_id.clone()));
+        assert_eq!(contract.balances.get(&(holder_id.clone(), class_id(0))).copied(), Some(5));
+        assert_eq!(contract.sft_balance_of(holder_id.clone(), class_id(1)).0, 2);
+        assert_eq!(contract.sft_class_count_of_owner(holder_id.clone()), 2);
+        assert_eq!(contract.sft_is_approved(holder_id.clone(), fan_id, class_id(0)).0, 3);
+        // The old market's approval of the contract is dropped rather than migrated
+        assert!(contract.approvals.get(&(holder_id.clone(), class_id(0), market_id)).is_none());
+        let inventory = contract.sft_inventory_of_owner(holder_id, None, None);
+        assert_eq!(inventory.get(&class_id(0)), Some(&U64(5)));
+        assert_eq!(inventory.get(&class_id(1)), Some(&U64(2)));
+    }
+
+    // Gives `holder_id` a legacy balance map of `count` classes, 10 copies each
+    fn fill_legacy_inventory(contract: &mut Contract, holder_id: &AccountId, count: u64) {
+        let legacy_balances: HashMap<String, u64> = (0..count).map(|index| (class_id(index), 10)).collect();
+        contract.legacy_owner_balances.insert(holder_id.clone(), legacy_balances);
+        contract.legacy_owners.insert(holder_id.clone());
+        contract.internal_flush();
+    }
+
+    #[test]
+    fn huge_legacy_account_migrates_in_chunks() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        let holder_id = account("holder.near");
+        fill_legacy_inventory(&mut contract, &holder_id, HUGE_LEGACY_CLASSES);
+        set_context(&holder_id, NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        // Spending copies moves only the class spent
+        transfer_gas(&mut contract, &holder_id, &account("receiver.near"), 4);
+        assert!(contract.is_legacy_account(holder_id.clone()));
+        assert_eq!(contract.sft_balance_of(holder_id.clone(), class_id(0)).0, 6);
+        assert_eq!(contract.sft_class_count_of_owner(holder_id.clone()), HUGE_LEGACY_CLASSES as u32);
+
+        // Receiving copies leaves the receiver's legacy map alone
+        contract.internal_flush();
+        set_context(&account("receiver.near"), NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+        set_context(&account("receiver.near"), NearToken::from_yoctonear(1));
+        contract.sft_transfer(holder_id.clone(), class_id(0), U64(3), None);
+        let legacy_balances = contract.legacy_owner_balances.get(&holder_id).unwrap();
+        assert_eq!(legacy_balances.len(), HUGE_LEGACY_CLASSES as usize - 1);
+        assert!(!legacy_balances.contains_key(&class_id(0)));
+        assert_eq!(contract.sft_balance_of(holder_id.clone(), class_id(0)).0, 9);
+
+        // Each chunk fits in one call though the whole map would not
+        let mut calls = 0;
+        let mut total_gas: u64 = 0;
+        loop {
+            contract.internal_flush();
+            set_context(&account("owner.near"), NearToken::from_near(10));
+            if contract.migrate_legacy_accounts(vec![holder_id.clone()], Some(DEFAULT_MIGRATION_CHUNK)) == 0 {
+                break;
+            }
+            contract.internal_flush();
+            total_gas += env::used_gas().as_gas();
+            calls += 1;
+        }
+        assert_eq!(calls, HUGE_LEGACY_CLASSES as u32 / DEFAULT_MIGRATION_CHUNK);
+        assert!(total_gas > Gas::from_tgas(300).as_gas(), "whole migration used {} gas", total_gas);
+        assert!(!contract.is_legacy_account(holder_id.clone()));
+        assert!(contract.legacy_owners(None, None).is_empty());
+        assert_eq!(contract.sft_class_count_of_owner(holder_id.clone()), HUGE_LEGACY_CLASSES as u32);
+        assert_eq!(contract.sft_balance_of(holder_id.clone(), class_id(0)).0, 9);
+        assert_eq!(contract.sft_balance_of(holder_id, class_id(HUGE_LEGACY_CLASSES - 1)).0, 10);
+    }
+
+    #[test]
+    fn transfer_migrates_legacy_sender() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        let holder_id = account("holder.near");
+        contract.legacy_owner_balances.insert(holder_id.clone(), HashMap::from([(class_id(0), 5)]));
+        set_context(&holder_id, NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        transfer_gas(&mut contract, &holder_id, &account("receiver.near"), 2);
+        assert!(!contract.is_legacy_account(holder_id.clone()));
+        assert_eq!(contract.sft_balance_of(holder_id, class_id(0)).0, 3);
+        assert_eq!(contract.sft_balance_of(account("receiver.near"), class_id(0)).0, 2);
+    }
+}
@@ -0,0 +1,101 @@
+// --- Burning and Class Retirement ---
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: destroys copies and credits the storage their balance entries used
+    fn internal_burn(
+        &mut self,
+        owner_id: &AccountId,
+        authorized_id: Option<AccountId>,
+        token_class_id: String,
+        amount: u64,
+        memo: Option<String>,
+    ) {
+        assert!(amount > 0, "Burn amount must be positive");
+        let initial_storage_usage = env::storage_usage();
+
+        if let Some(approved_account_id) = authorized_id.as_ref() {
+            self.internal_use_approval(owner_id, approved_account_id, &token_class_id, amount);
+        }
+        self.internal_withdraw(owner_id, &token_class_id, amount, owner_id);
+
+        let current_supply = self.token_supply.get(&token_class_id).copied()
+            .expect("Token class ID does not exist");
+        self.token_supply.insert(token_class_id.clone(), current_supply - amount);
+
+        // Freed bytes go back to the storage balance of the owner whose entry was cleared
+        self.internal_charge_storage(owner_id, initial_storage_usage, 0);
+
+        MtEvent::MtBurn(vec![MtBurnLog {
+            owner_id: owner_id.clone(),
+            authorized_id,
+            token_ids: vec![token_class_id],
+            amounts: vec![U128(u128::from(amount))],
+            memo,
+        }]).emit();
+    }
+
+    /// Burns copies held by the predecessor, reducing the class's total supply.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_burn(&mut self, token_class_id: String, amount: U64, memo: Option<String>) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        self.internal_burn(&owner_id, None, token_class_id, amount.0, memo);
+    }
+
+    /// Burns copies from `owner_id` using the predecessor's approval for the class.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_burn_from(
+        &mut self,
+        owner_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let approved_account_id = env::predecessor_account_id();
+        assert_ne!(owner_id, approved_account_id, "Owner cannot burn from themselves using this method");
+        self.internal_burn(&owner_id, Some(approved_account_id), token_class_id, amount.0, memo);
+    }
+
+    /// Freezes a token class against further minting. Existing copies keep trading and can still be burned.
+    /// Only the class creator or the contract owner can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    /// Requires at least 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn retire_class(&mut self, token_class_id: String) {
+        assert_at_least_one_yocto();
+        let caller_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let creator_id = self.creators.get(&token_class_id)
+            .expect("Token class ID does not exist");
+        assert!(
+            caller_id == *creator_id || caller_id == self.owner_id,
+            "Only the class creator or contract owner can retire a token class"
+        );
+        assert!(
+            self.retired_classes.insert(token_class_id.clone()),
+            "Token class is already retired"
+        );
+
+        let final_supply = self.token_supply.get(&token_class_id).copied().unwrap_or(0);
+        MusicSftEvent::ClassRetire(vec![ClassRetireLog {
+            token_class_id,
+            retired_by: caller_id,
+            final_supply: U128(u128::from(final_supply)),
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    pub fn sft_is_retired(&self, token_class_id: String) -> bool {
+        self.retired_classes.contains(&token_class_id)
+    }
+}
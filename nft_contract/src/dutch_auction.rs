@@ -0,0 +1,78 @@
+// --- Dutch Auction Drops ---
+// A class created with a descending price schedule goes on primary sale right away. Its price
+// starts at `start_price` and drops by `price_decrement` every `decrement_interval` until it
+// reaches `floor_price`, so buyers decide when the edition is worth minting.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    pub start_price: U128, // Price per copy when the drop opens
+    pub floor_price: U128, // Price per copy never goes below this
+    pub price_decrement: U128, // Taken off the price every interval
+    pub decrement_interval: U64, // In nanoseconds
+    #[serde(default)]
+    pub starts_at: U64, // Block timestamp in nanoseconds, set when the class is created
+}
+
+impl DutchAuction {
+    pub fn assert_valid(&self) {
+        assert!(self.start_price.0 >= self.floor_price.0, "Start price cannot be below the floor price");
+        assert!(self.price_decrement.0 > 0, "Price decrement must be positive");
+        assert!(self.decrement_interval.0 > 0, "Decrement interval must be positive");
+    }
+
+    // Price per copy at the given block timestamp
+    pub fn price_at(&self, timestamp: u64) -> u128 {
+        let steps = timestamp.saturating_sub(self.starts_at.0) / self.decrement_interval.0;
+        let discount = self.price_decrement.0.saturating_mul(u128::from(steps));
+        self.start_price.0.saturating_sub(discount).max(self.floor_price.0)
+    }
+}
+
+// Live state of a Dutch auction drop
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuctionInfo {
+    pub token_class_id: String,
+    pub schedule: DutchAuction,
+    pub current_price: U128,
+    pub remaining: U64, // Copies left before the max supply is reached
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: the price per copy buyers pay on primary sale right now
+    pub(crate) fn internal_primary_price(&self, token_class_id: &str) -> u128 {
+        if let Some(auction) = self.dutch_auctions.get(token_class_id) {
+            return auction.price_at(env::block_timestamp());
+        }
+        self.token_metadata.get(token_class_id)
+            .expect("Token class metadata not found")
+            .price_per_copy.0
+    }
+
+    /// Returns the price per copy a primary sale buyer pays right now.
+    pub fn sft_primary_price(&self, token_class_id: String) -> U128 {
+        U128(self.internal_primary_price(&token_class_id))
+    }
+
+    /// Returns the schedule, live price and remaining copies of a class's Dutch auction drop.
+    pub fn sft_dutch_auction(&self, token_class_id: String) -> Option<DutchAuctionInfo> {
+        let schedule = self.dutch_auctions.get(&token_class_id)?.clone();
+        let max_supply = self.max_supply.get(&token_class_id).copied().unwrap_or(0);
+        let remaining = max_supply.saturating_sub(self.internal_minted(&token_class_id));
+        Some(DutchAuctionInfo {
+            current_price: U128(schedule.price_at(env::block_timestamp())),
+            token_class_id,
+            schedule,
+            remaining: U64(remaining),
+        })
+    }
+}
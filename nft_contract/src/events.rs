@@ -0,0 +1,313 @@
+// --- NEP-297 Events ---
+// Token movements are reported with the NEP-245 event family so standard indexers
+// understand them; everything marketplace or class specific uses our own standard.
+// Each variant carries a list of data entries, so a batch is logged once.
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId};
+
+use crate::{DutchAuction, NFTMetadata, Presale, PurchaseRule, SaleWindow, SplitShare};
+
+pub const NEP245_STANDARD_NAME: &str = "nep245";
+pub const NEP245_VERSION: &str = "1.0.0";
+pub const MUSIC_SFT_STANDARD_NAME: &str = "music_sft";
+pub const MUSIC_SFT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    #[serde(flatten)]
+    event: &'a T,
+}
+
+// Helper to log a NEP-297 event under the given standard
+fn emit_event<T: Serialize>(standard: &str, version: &str, event: &T) {
+    let event_log = EventLog { standard, version, event };
+    match near_sdk::serde_json::to_string(&event_log) {
+        Ok(json_string) => env::log_str(&format!("EVENT_JSON:{}", json_string)),
+        Err(e) => env::log_str(&format!("Failed to serialize NEP-297 event log: {}", e)),
+    }
+}
+
+// --- NEP-245 event family ---
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtMintLog {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtBurnLog {
+    pub owner_id: AccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtTransferLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>, // Set when an approved account moved the copies
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<String>,
+    pub amounts: Vec<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum MtEvent {
+    MtMint(Vec<MtMintLog>),
+    MtBurn(Vec<MtBurnLog>),
+    MtTransfer(Vec<MtTransferLog>),
+}
+
+impl MtEvent {
+    pub fn emit(&self) {
+        emit_event(NEP245_STANDARD_NAME, NEP245_VERSION, self);
+    }
+}
+
+// --- Project specific event family ---
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClassCreateLog {
+    pub token_class_id: String,
+    pub creator_id: AccountId,
+    pub metadata: NFTMetadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_supply: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub royalty_bps: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dutch_auction: Option<DutchAuction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sale_window: Option<SaleWindow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presale: Option<Presale>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClassRetireLog {
+    pub token_class_id: String,
+    pub retired_by: AccountId,
+    pub final_supply: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MinterLog {
+    pub token_class_id: String,
+    pub minter_id: AccountId,
+    pub creator_id: AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SplitSheetLog {
+    pub token_class_id: String,
+    pub account_id: AccountId, // Who proposed, approved or cancelled
+    pub shares: Vec<SplitShare>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApproveLog {
+    pub owner_id: AccountId,
+    pub approved_account_id: AccountId,
+    pub token_class_id: String,
+    pub amount: U128, // Allowance after the change
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RevokeLog {
+    pub owner_id: AccountId,
+    pub revoked_account_id: AccountId,
+    pub token_class_id: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingUpdateLog {
+    pub seller_id: AccountId,
+    pub token_class_id: String,
+    pub amount: U128, // Copies available for sale, 0 when delisted
+    pub price_per_copy: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>, // Payment token, NEAR when absent
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferUpdateLog {
+    pub buyer_id: AccountId,
+    pub token_class_id: String,
+    pub amount: U128, // Copies still wanted, 0 when cancelled or filled
+    pub price_per_copy: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<U64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionLog {
+    pub auction_id: U64,
+    pub seller_id: AccountId,
+    pub token_class_id: String,
+    pub amount: U128, // Copies sold together as one lot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bidder_id: Option<AccountId>, // Highest bidder, if any
+    pub bid: U128, // Highest bid, or the reserve price before the first bid
+    pub ends_at: U64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PrimarySaleUpdateLog {
+    pub token_class_id: String,
+    pub enabled: bool,
+    pub max_supply: U64,
+    pub price_per_copy: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleWindowLog {
+    pub token_class_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<U64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PresaleLog {
+    pub token_class_id: String,
+    pub presale: Option<Presale>, // None when the presale was removed
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AllowlistLog {
+    pub token_class_id: String,
+    pub account_ids: Vec<AccountId>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseRulesLog {
+    pub token_class_id: String,
+    pub rules: Vec<PurchaseRule>, // Empty when the rules were removed
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleLog {
+    pub seller_id: AccountId,
+    pub buyer_id: AccountId,
+    pub token_class_id: String,
+    pub amount: U128,
+    pub price: U128, // Total paid for all copies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub royalty: Option<U128>, // Part of the price paid to the split holders on secondary sales
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_fee: Option<U128>, // Part of the price kept by the platform treasury
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>, // Payment token, NEAR when absent
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BalanceCreditLog {
+    pub account_id: AccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_class_id: Option<String>, // Class whose sale produced the credit
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>, // Token credited, NEAR when absent
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MarketFeeUpdateLog {
+    pub fee_bps: u16,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTokenLog {
+    pub ft_token_id: AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryWithdrawLog {
+    pub receiver_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>, // Token withdrawn, NEAR when absent
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum MusicSftEvent {
+    ClassCreate(Vec<ClassCreateLog>),
+    ClassRetire(Vec<ClassRetireLog>),
+    MinterAdd(Vec<MinterLog>),
+    MinterRemove(Vec<MinterLog>),
+    SplitSheetPropose(Vec<SplitSheetLog>),
+    SplitSheetApprove(Vec<SplitSheetLog>),
+    SplitSheetCancel(Vec<SplitSheetLog>),
+    SplitSheetUpdate(Vec<SplitSheetLog>),
+    Approve(Vec<ApproveLog>),
+    Revoke(Vec<RevokeLog>),
+    ListingUpdate(Vec<ListingUpdateLog>),
+    Sale(Vec<SaleLog>),
+    OfferUpdate(Vec<OfferUpdateLog>),
+    AuctionCreate(Vec<AuctionLog>),
+    AuctionBid(Vec<AuctionLog>),
+    AuctionCancel(Vec<AuctionLog>),
+    AuctionSettle(Vec<AuctionLog>),
+    PrimarySaleUpdate(Vec<PrimarySaleUpdateLog>),
+    PrimarySale(Vec<SaleLog>),
+    SaleWindowUpdate(Vec<SaleWindowLog>),
+    PresaleUpdate(Vec<PresaleLog>),
+    AllowlistAdd(Vec<AllowlistLog>),
+    AllowlistRemove(Vec<AllowlistLog>),
+    PurchaseRulesUpdate(Vec<PurchaseRulesLog>),
+    MarketFeeUpdate(Vec<MarketFeeUpdateLog>),
+    FtTokenAccept(Vec<FtTokenLog>),
+    FtTokenRemove(Vec<FtTokenLog>),
+    TreasuryWithdraw(Vec<TreasuryWithdrawLog>),
+    BalanceCredit(Vec<BalanceCreditLog>),
+    Withdraw(Vec<BalanceCreditLog>),
+}
+
+impl MusicSftEvent {
+    pub fn emit(&self) {
+        emit_event(MUSIC_SFT_STANDARD_NAME, MUSIC_SFT_VERSION, self);
+    }
+}
@@ -0,0 +1,267 @@
+// --- NEP-141 Payments ---
+// Listings can be priced in a fungible token (e.g. USDC) instead of NEAR. The buyer calls
+// `ft_transfer_call` on the token contract with a purchase order as `msg`; the token contract
+// then calls `ft_on_transfer` here. Seller and split holder shares are pushed with `ft_transfer`
+// right away, and the platform fee stays on the contract in a per-token treasury. A share whose
+// `ft_transfer` fails (e.g. the receiver is not registered with the token) is parked on a ledger
+// and can be pulled later with `withdraw_ft`. Only token contracts on the owner's allowlist can
+// price listings or pay for them, so no other contract can pose as a listing's token.
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, Gas, NearToken, Promise, PromiseOrValue, PromiseResult};
+
+use crate::*;
+
+pub const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(10);
+pub const GAS_FOR_FT_TRANSFER_CALLBACK: Gas = Gas::from_tgas(5);
+
+// Interface of the NEP-141 token contracts we pay out with
+#[ext_contract(ext_ft)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// Purchase order carried in the `msg` of `ft_transfer_call`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtPurchaseOrder {
+    pub token_class_id: String,
+    pub seller_id: AccountId,
+    pub quantity: Option<U64>, // Defaults to 1 copy
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: panics unless the owner accepts payments in this token
+    pub(crate) fn assert_accepted_ft_token(&self, ft_token_id: &AccountId) {
+        assert!(
+            self.accepted_ft_tokens.contains(ft_token_id),
+            "Token {} is not accepted for payments", ft_token_id
+        );
+    }
+
+    // Internal helper: sends `amount` of a token and parks it on the ledger if delivery fails,
+    // charging the ledger entry to `storage_payer`
+    fn internal_ft_send(
+        &self,
+        ft_token_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: u128,
+        memo: Option<String>,
+        storage_payer: &AccountId,
+    ) -> Promise {
+        ext_ft::ext(ft_token_id.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id.clone(), U128(amount), memo)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FT_TRANSFER_CALLBACK)
+                    .on_ft_payout(ft_token_id.clone(), receiver_id.clone(), U128(amount), storage_payer.clone()),
+            )
+    }
+
+    /// NEP-141 receiver: buys copies from a listing priced in the calling token.
+    /// `msg` must be an `FtPurchaseOrder` in JSON. Returns the tokens left over after paying the
+    /// listing price, which the token contract refunds to the buyer. Any failure refunds everything.
+    /// Only token contracts the owner accepts can call this.
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let ft_token_id = env::predecessor_account_id();
+        self.assert_accepted_ft_token(&ft_token_id);
+        let order: FtPurchaseOrder = near_sdk::serde_json::from_str(&msg)
+            .unwrap_or_else(|_| env::panic_str("msg must be a purchase order"));
+        let amount_to_buy: u64 = order.quantity.map(|quantity| quantity.0).unwrap_or(1);
+        let token_class_id = order.token_class_id;
+        let seller_id = order.seller_id;
+        let initial_storage_usage = env::storage_usage();
+
+        let total_price = self.internal_fill_listing(
+            &sender_id,
+            &seller_id,
+            &token_class_id,
+            amount_to_buy,
+            Some(&ft_token_id),
+        );
+        if amount.0 < total_price {
+            env::panic_str(&format!(
+                "Insufficient payment. Sent: {}, Required: {}", amount.0, total_price
+            ));
+        }
+
+        // --- Collect Platform Fee, Pay Royalties & Seller ---
+        let market_fee = self.internal_collect_market_fee(&token_class_id, total_price, Some(&ft_token_id));
+        let proceeds = total_price - market_fee;
+        let payout = self.internal_compute_payout(&token_class_id, &seller_id, proceeds);
+        let royalty = self.internal_royalty_shares(&token_class_id, proceeds)
+            .iter()
+            .map(|(_, share)| share)
+            .sum::<u128>();
+        for (receiver_id, share) in payout {
+            if share > 0 {
+                self.internal_ft_send(
+                    &ft_token_id,
+                    &receiver_id,
+                    share,
+                    Some(format!("Sale of {}", token_class_id)),
+                    &sender_id,
+                );
+            }
+        }
+
+        // No NEAR comes with the tokens, so the buyer's storage balance must already cover their entries
+        self.internal_charge_storage(&sender_id, initial_storage_usage, 0);
+
+        MusicSftEvent::Sale(vec![SaleLog {
+            seller_id,
+            buyer_id: sender_id,
+            token_class_id,
+            amount: U128(u128::from(amount_to_buy)),
+            price: U128(total_price),
+            royalty: Some(U128(royalty)),
+            market_fee: Some(U128(market_fee)),
+            ft_token_id: Some(ft_token_id),
+        }]).emit();
+
+        PromiseOrValue::Value(U128(amount.0 - total_price))
+    }
+
+    /// Callback after an `ft_transfer` payout: parks the amount on the ledger if the transfer failed.
+    /// A new ledger entry is charged to `storage_payer`, the account whose call sent the payout.
+    /// Returns whether the tokens were delivered.
+    #[private]
+    pub fn on_ft_payout(&mut self, ft_token_id: AccountId, receiver_id: AccountId, amount: U128, storage_payer: AccountId) -> bool {
+        let delivered = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !delivered {
+            let initial_storage_usage = env::storage_usage();
+            self.ft_pending_withdrawals
+                .entry((receiver_id.clone(), ft_token_id.clone()))
+                .or_insert_with(|| PendingBalance { amount: 0, storage_payer: Some(storage_payer.clone()) })
+                .amount += amount.0;
+            self.internal_charge_storage_owed(&storage_payer, initial_storage_usage, 0);
+            MusicSftEvent::BalanceCredit(vec![BalanceCreditLog {
+                account_id: receiver_id,
+                token_class_id: None,
+                amount,
+                ft_token_id: Some(ft_token_id),
+            }]).emit();
+        }
+        delivered
+    }
+
+    /// Retries delivery of the predecessor's parked proceeds in a fungible token.
+    /// The ledger entry's storage goes back to whoever paid for it.
+    /// Register with the token contract first, or the transfer fails and the funds are parked again.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn withdraw_ft(&mut self, ft_token_id: AccountId) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let key = (account_id.clone(), ft_token_id.clone());
+        let pending = self.ft_pending_withdrawals.get(&key).cloned().expect("Nothing to withdraw");
+        self.internal_remove_paid_by(pending.storage_payer.as_ref(), |this| {
+            this.ft_pending_withdrawals.remove(&key);
+        });
+        let amount = pending.amount;
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        MusicSftEvent::Withdraw(vec![BalanceCreditLog {
+            account_id: account_id.clone(),
+            token_class_id: None,
+            amount: U128(amount),
+            ft_token_id: Some(ft_token_id.clone()),
+        }]).emit();
+        self.internal_ft_send(&ft_token_id, &account_id, amount, None, &account_id)
+    }
+
+    /// Sends accrued fees in a fungible token, all of them when `amount` is omitted.
+    /// Only the contract owner can call this; tokens go to the owner unless `receiver_id` is given.
+    /// If the transfer fails the callback puts the fees back in the token's treasury.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn withdraw_ft_treasury(&mut self, ft_token_id: AccountId, amount: Option<U128>, receiver_id: Option<AccountId>) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let balance = self.ft_treasury_balances.get(&ft_token_id).copied().unwrap_or(0);
+        let amount = amount.map(|amount| amount.0).unwrap_or(balance);
+        assert!(amount > 0, "Nothing to withdraw");
+        assert!(amount <= balance, "Amount exceeds the treasury balance");
+        let receiver_id = receiver_id.unwrap_or_else(|| self.owner_id.clone());
+
+        self.ft_treasury_balances.insert(ft_token_id.clone(), balance - amount);
+        ext_ft::ext(ft_token_id.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(receiver_id.clone(), U128(amount), None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FT_TRANSFER_CALLBACK)
+                    .on_withdraw_ft_treasury(ft_token_id, receiver_id, U128(amount)),
+            )
+    }
+
+    /// Callback after `withdraw_ft_treasury`: restores the token's treasury balance if the transfer failed.
+    /// Returns whether the tokens were delivered.
+    #[private]
+    pub fn on_withdraw_ft_treasury(&mut self, ft_token_id: AccountId, receiver_id: AccountId, amount: U128) -> bool {
+        let delivered = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if delivered {
+            MusicSftEvent::TreasuryWithdraw(vec![TreasuryWithdrawLog {
+                receiver_id,
+                amount,
+                ft_token_id: Some(ft_token_id),
+            }]).emit();
+        } else {
+            *self.ft_treasury_balances.entry(ft_token_id).or_insert(0) += amount.0;
+        }
+        delivered
+    }
+
+    /// Accepts a NEP-141 token contract as a listing currency. Only the contract owner can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn add_accepted_ft_token(&mut self, ft_token_id: AccountId) {
+        self.assert_owner();
+        let initial_storage_usage = env::storage_usage();
+        assert!(self.accepted_ft_tokens.insert(ft_token_id.clone()), "Token is already accepted");
+        MusicSftEvent::FtTokenAccept(vec![FtTokenLog { ft_token_id }]).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Stops accepting a NEP-141 token contract. Its listings stay but cannot be bought until it is
+    /// accepted again. Only the contract owner can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn remove_accepted_ft_token(&mut self, ft_token_id: AccountId) {
+        assert_one_yocto();
+        self.assert_owner();
+        let initial_storage_usage = env::storage_usage();
+        assert!(self.accepted_ft_tokens.remove(&ft_token_id), "Token is not accepted");
+        MusicSftEvent::FtTokenRemove(vec![FtTokenLog { ft_token_id }]).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Get a page of the NEP-141 token contracts accepted as listing currencies
+    pub fn get_accepted_ft_tokens(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(self.accepted_ft_tokens.len() as u64);
+
+        self.accepted_ft_tokens
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // Get the fees accrued in a fungible token and not yet withdrawn
+    pub fn get_ft_treasury_balance(&self, ft_token_id: AccountId) -> U128 {
+        U128(self.ft_treasury_balances.get(&ft_token_id).copied().unwrap_or(0))
+    }
+
+    // Get the token proceeds an account can withdraw with `withdraw_ft`
+    pub fn get_ft_pending_withdrawal(&self, account_id: AccountId, ft_token_id: AccountId) -> U128 {
+        U128(self.ft_pending_withdrawals.get(&(account_id, ft_token_id)).map(|pending| pending.amount).unwrap_or(0))
+    }
+}
@@ -0,0 +1,115 @@
+// --- Token-Gated Purchases ---
+// A creator can require buyers of a class to hold copies of other classes, e.g. owning the
+// album unlocks its deluxe bonus track. Every rule must hold for the receiving account when
+// copies are minted to it and when it buys on the marketplace.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+pub const MAX_PURCHASE_RULES: usize = 5;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseRule {
+    pub required_class_id: String,
+    pub min_balance: U64, // Copies of `required_class_id` the buyer must hold
+}
+
+// How an account measures up against one purchase rule
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseRuleStatus {
+    pub required_class_id: String,
+    pub min_balance: U64,
+    pub balance: U64, // Copies the account holds now
+    pub satisfied: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseEligibility {
+    pub token_class_id: String,
+    pub account_id: AccountId,
+    pub eligible: bool,
+    pub rules: Vec<PurchaseRuleStatus>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: checks every purchase rule of a class against an account's balances
+    fn internal_purchase_rule_statuses(&self, token_class_id: &str, account_id: &AccountId) -> Vec<PurchaseRuleStatus> {
+        let Some(rules) = self.purchase_rules.get(token_class_id) else {
+            return Vec::new();
+        };
+        rules.iter()
+            .map(|rule| {
+                let balance = self.internal_balance_of(account_id, &rule.required_class_id);
+                PurchaseRuleStatus {
+                    required_class_id: rule.required_class_id.clone(),
+                    min_balance: rule.min_balance,
+                    balance: U64(balance),
+                    satisfied: balance >= rule.min_balance.0,
+                }
+            })
+            .collect()
+    }
+
+    // Internal helper: panics unless the account satisfies every purchase rule of the class
+    pub(crate) fn assert_purchase_rules(&self, token_class_id: &str, account_id: &AccountId) {
+        for status in self.internal_purchase_rule_statuses(token_class_id, account_id) {
+            if !status.satisfied {
+                env::panic_str(&format!(
+                    "{} must hold at least {} copies of {} to buy {}",
+                    account_id, status.min_balance.0, status.required_class_id, token_class_id
+                ));
+            }
+        }
+    }
+
+    /// Replaces the purchase rules of a class; an empty list removes them.
+    /// Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn sft_set_purchase_rules(&mut self, token_class_id: String, rules: Vec<PurchaseRule>) {
+        self.assert_class_creator(&token_class_id);
+        assert!(rules.len() <= MAX_PURCHASE_RULES, "A class cannot have more than {} purchase rules", MAX_PURCHASE_RULES);
+        for rule in rules.iter() {
+            assert_ne!(rule.required_class_id, token_class_id, "A class cannot require itself");
+            assert!(
+                self.token_classes.contains(&rule.required_class_id),
+                "Required token class {} does not exist", rule.required_class_id
+            );
+            assert!(rule.min_balance.0 > 0, "Required balance must be positive");
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        if rules.is_empty() {
+            self.purchase_rules.remove(&token_class_id);
+        } else {
+            self.purchase_rules.insert(token_class_id.clone(), rules.clone());
+        }
+        MusicSftEvent::PurchaseRulesUpdate(vec![PurchaseRulesLog { token_class_id, rules }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Get the purchase rules of a token class
+    pub fn sft_purchase_rules(&self, token_class_id: String) -> Vec<PurchaseRule> {
+        self.purchase_rules.get(&token_class_id).cloned().unwrap_or_default()
+    }
+
+    /// Returns whether an account can buy or receive newly minted copies of a class, rule by rule.
+    pub fn sft_purchase_eligibility(&self, token_class_id: String, account_id: AccountId) -> PurchaseEligibility {
+        let rules = self.internal_purchase_rule_statuses(&token_class_id, &account_id);
+        PurchaseEligibility {
+            eligible: rules.iter().all(|status| status.satisfied),
+            token_class_id,
+            account_id,
+            rules,
+        }
+    }
+}
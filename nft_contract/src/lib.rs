@@ -1,11 +1,53 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::store::{LookupMap, UnorderedSet};
+#[allow(deprecated)]
+use near_sdk::store::{IterableSet, LookupMap, LookupSet, TreeMap, UnorderedSet};
 // Import standard HashMap
 use std::collections::HashMap;
-use near_sdk::json_types::U64;
+use near_sdk::json_types::{U64, U128};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Promise, NearToken, BorshStorageKey, log};
-use serde_json;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, CryptoHash, Gas, PanicOnDefault, PromiseOrValue, PromiseResult, BorshStorageKey};
+
+mod auctions;
+mod balance_migration;
+mod burn;
+mod dutch_auction;
+mod events;
+mod gating;
+mod ft_payments;
+mod marketplace;
+mod minters;
+mod mt_core;
+mod mt_metadata;
+mod offers;
+mod presale;
+mod primary_sale;
+mod royalties;
+mod sale_windows;
+mod splits;
+mod storage;
+mod treasury;
+mod upgrade;
+mod withdrawals;
+#[cfg(test)]
+mod test_utils;
+
+pub use crate::auctions::*;
+pub use crate::dutch_auction::*;
+pub use crate::events::*;
+pub use crate::ft_payments::*;
+pub use crate::gating::*;
+pub use crate::marketplace::*;
+pub use crate::mt_core::*;
+pub use crate::mt_metadata::*;
+pub use crate::offers::*;
+pub use crate::presale::*;
+pub use crate::royalties::*;
+pub use crate::sale_windows::*;
+pub use crate::splits::*;
+pub use crate::storage::*;
+pub use crate::treasury::*;
+pub use crate::upgrade::*;
+pub use crate::withdrawals::*;
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
 #[serde(crate = "near_sdk::serde")]
@@ -14,7 +56,7 @@ pub struct NFTMetadata {
     pub description: String,
     pub media: String, // URL to the MP3 file
     pub media_hash: String,
-    pub price_per_copy: near_sdk::json_types::U128, // Changed to U128 for JSON compatibility
+    pub price_per_copy: U128, // Changed to U128 for JSON compatibility
     pub cover_photo: String,
 }
 
@@ -26,6 +68,32 @@ pub struct SftMintArgs {
     pub amount: U64,
     pub receiver_id: AccountId,
     pub metadata: Option<NFTMetadata>,
+    // Fixed edition size, only accepted together with metadata when creating a class
+    #[serde(default)]
+    pub max_supply: Option<U64>,
+    // Creator's share of every secondary sale in basis points, only accepted when creating a class
+    #[serde(default)]
+    pub royalty_bps: Option<u16>,
+    // Opens a primary sale with a descending price right away, requires max_supply
+    #[serde(default)]
+    pub dutch_auction: Option<DutchAuction>,
+    // Announces the release: primary sales and marketplace trading wait for its start
+    #[serde(default)]
+    pub sale_window: Option<SaleWindow>,
+    // Limits the primary sale to allowlisted accounts until the presale ends
+    #[serde(default)]
+    pub presale: Option<Presale>,
+}
+
+// Minted vs. remaining copies for a token class
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SupplyInfo {
+    pub token_class_id: String,
+    pub total_supply: U64, // Copies currently in circulation
+    pub minted: U64, // Copies ever minted, burned ones included
+    pub max_supply: Option<U64>,
+    pub remaining: Option<U64>, // None when the class is uncapped
 }
 
 // Add this new struct to combine class ID and metadata for return
@@ -37,64 +105,37 @@ pub struct TokenClassMetadata {
     pub creator_id: AccountId,
 }
 
-// --- NEP-297 Event Standard --- 
-
-#[derive(Serialize, Debug)]
-#[serde(crate = "near_sdk::serde")]
-struct Nep297Event<'a> {
-    standard: &'a str, // Always "nep297"
-    version: &'a str,  // e.g., "1.0.0"
-    event: &'a str,    // e.g., "sft_transfer"
-    data: Vec<serde_json::Value>, // Array of event data structs
-}
-
-#[derive(Serialize, Debug)]
-#[serde(crate = "near_sdk::serde")]
-struct SftTransferLogData {
-    owner_id: AccountId,
-    receiver_id: AccountId,
-    token_class_id: String,
-    amount: U64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    memo: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    authorized_id: Option<AccountId>, // For transfer_from
-}
+pub const GAS_FOR_SFT_TRANSFER_CALL: Gas = Gas::from_tgas(25); // Kept for the rest of the calling method
 
-#[derive(Serialize, Debug)]
-#[serde(crate = "near_sdk::serde")]
-struct SftApproveLogData {
-    owner_id: AccountId,
-    approved_account_id: AccountId,
-    token_class_id: String,
-    amount: U64,
-}
-
-#[derive(Serialize, Debug)]
-#[serde(crate = "near_sdk::serde")]
-struct SftRevokeLogData {
-    owner_id: AccountId,
-    revoked_account_id: AccountId,
-    token_class_id: String,
+// Hook implemented by contracts that accept SFT copies through `sft_transfer_call`.
+// Returns the number of copies that should be given back to the previous owner.
+#[ext_contract(ext_sft_receiver)]
+pub trait SftReceiver {
+    fn sft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        msg: String,
+    ) -> PromiseOrValue<U64>;
 }
 
-// Helper to log NEP-297 events
-fn log_nep297_event<T: Serialize>(event_name: &str, data: T) {
-    match serde_json::to_value(&data) {
-        Ok(value) => {
-            let event_log = Nep297Event {
-                standard: "nep297", // Should match the specific SFT event standard if one emerges, else use nep297 as generic
-                version: "1.0.0",
-                event: event_name,
-                data: vec![value],
-            };
-            match serde_json::to_string(&event_log) {
-                Ok(json_string) => env::log_str(&format!("EVENT_JSON:{}", json_string)),
-                Err(e) => log!("Failed to serialize NEP-297 event log: {}", e),
-            }
-        },
-        Err(e) => log!("Failed to serialize NEP-297 event data: {}", e),
-    }
+// Validates parallel class ID / amount lists for batch calls and returns the raw amounts
+fn assert_batch_args(token_class_ids: &[String], amounts: &[U64]) -> Vec<u64> {
+    assert!(!token_class_ids.is_empty(), "Token class IDs cannot be empty");
+    assert_eq!(
+        token_class_ids.len(),
+        amounts.len(),
+        "Token class IDs and amounts must have the same length"
+    );
+    amounts
+        .iter()
+        .map(|amount| {
+            assert!(amount.0 > 0, "Transfer amount must be positive");
+            amount.0
+        })
+        .collect()
 }
 
 // Storage Keys Enum
@@ -106,45 +147,207 @@ enum StorageKey {
     TokenClasses,
     Approvals,
     CreatorMap,
+    OwnerClasses, // The `updated_lib.rs` owner set, carried over as `legacy_owners`
+    MaxSupply,
+    PrimarySaleClasses,
+    Minted,
+    ClassMinters,
+    Listings,
+    ListingsByClass,
+    ListingsByClassInner { token_class_hash: CryptoHash },
+    ListingsBySeller,
+    ListingsBySellerInner { account_hash: CryptoHash },
+    Royalties,
+    SplitSheets,
+    SplitProposals,
+    ClassFeeTotals,
+    PendingWithdrawals,
+    FtTreasuryBalances,
+    FtPendingWithdrawals,
+    Offers,
+    OffersByClass,
+    OffersByClassInner { token_class_hash: CryptoHash },
+    Auctions,
+    AuctionsByClass,
+    AuctionsByClassInner { token_class_hash: CryptoHash },
+    DutchAuctions,
+    SaleWindows,
+    UpcomingDrops,
+    Presales,
+    PresaleAllowlist,
+    PresalePurchases,
+    PurchaseRules,
+    StorageAccounts,
+    Balances,
+    ClassesByOwner,
+    ClassesByOwnerInner { account_hash: CryptoHash },
+    AccountApprovals,
+    RetiredClasses,
+    AcceptedFtTokens,
+    BalanceStoragePayers,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[allow(deprecated)] // UnorderedSet layout is kept for deployed state compatibility
 pub struct Contract {
     pub owner_id: AccountId,
     // Maps Token Class ID (e.g., "city-song-edition") -> Metadata
     pub token_metadata: LookupMap<String, NFTMetadata>,
     // Maps Token Class ID -> Total Supply as u64
     pub token_supply: LookupMap<String, u64>,
-    // Maps Owner Account ID -> (Map of Token Class ID -> Balance as u64)
-    pub owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
+    // Legacy Owner Account ID -> (Map of Token Class ID -> Balance) maps, moved into `balances` account by account
+    pub legacy_owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
     // Set to track all existing token class IDs
     pub token_classes: UnorderedSet<String>,
-    // Map for approvals: Owner -> TokenClass -> ApprovedAccount -> Amount
-    pub approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
+    // Legacy approval maps: Owner -> TokenClass -> ApprovedAccount -> Amount, moved into `approvals` with the balances
+    pub legacy_approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
     // Maps Token Class ID -> Creator Account ID
     pub creators: LookupMap<String, AccountId>,
+    // Token Class IDs frozen against further minting
+    pub retired_classes: LookupSet<String>,
+    // Maps Token Class ID -> Maximum number of copies that can ever be minted
+    pub max_supply: LookupMap<String, u64>,
+    // Token Class IDs open for primary sales via `buy_primary`
+    pub primary_sale_classes: LookupSet<String>,
+    // Maps Token Class ID -> Copies ever minted (burns don't free up a capped edition)
+    pub minted: LookupMap<String, u64>,
+    // Maps Token Class ID -> Accounts the creator allowed to mint more copies
+    pub class_minters: LookupMap<String, Vec<AccountId>>,
+    // Maps (Seller, Token Class ID) -> Listing
+    pub listings: LookupMap<(AccountId, String), Listing>,
+    // Maps Token Class ID -> Sellers with a listing for it
+    pub listings_by_class: LookupMap<String, IterableSet<AccountId>>,
+    // Maps Seller -> Token Class IDs they have listed
+    pub listings_by_seller: LookupMap<AccountId, IterableSet<String>>,
+    // Maps Token Class ID -> Creator royalty in basis points
+    pub royalties: LookupMap<String, u16>,
+    // Maps Token Class ID -> Collaborator shares of its revenue
+    pub split_sheets: LookupMap<String, Vec<SplitShare>>,
+    // Maps Token Class ID -> Pending split sheet change awaiting consent
+    pub split_proposals: LookupMap<String, SplitProposal>,
+    // Most accounts a payout may pay, split holders and seller included
+    pub max_payout_recipients: u32,
+    // Marketplace fee in basis points taken from every secondary sale
+    pub market_fee_bps: u16,
+    // Fees accrued to the platform and not yet withdrawn, in yoctoNEAR
+    pub treasury_balance: u128,
+    // Maps (Token Class ID, FT Contract ID or None for NEAR) -> Total fees collected on its sales in that currency
+    pub class_fee_totals: LookupMap<(String, Option<AccountId>), u128>,
+    // Maps Account ID -> Sale proceeds waiting to be withdrawn, in yoctoNEAR
+    pub pending_withdrawals: LookupMap<AccountId, PendingBalance>,
+    // Maps FT Contract ID -> Fees accrued in that token and not yet withdrawn
+    pub ft_treasury_balances: LookupMap<AccountId, u128>,
+    // Maps (Account ID, FT Contract ID) -> Token proceeds whose `ft_transfer` failed, waiting to be withdrawn
+    pub ft_pending_withdrawals: LookupMap<(AccountId, AccountId), PendingBalance>,
+    // NEP-141 token contracts the owner accepts as listing currencies
+    pub accepted_ft_tokens: IterableSet<AccountId>,
+    // Maps (Buyer, Token Class ID) -> Offer with its price escrowed on the contract
+    pub offers: LookupMap<(AccountId, String), Offer>,
+    // Maps Token Class ID -> Buyers with an offer for it
+    pub offers_by_class: LookupMap<String, IterableSet<AccountId>>,
+    // Maps Auction ID -> Running auction with its copies and highest bid escrowed on the contract
+    pub auctions: LookupMap<u64, Auction>,
+    // Maps Token Class ID -> IDs of its running auctions
+    pub auctions_by_class: LookupMap<String, IterableSet<u64>>,
+    // ID given to the next auction
+    pub next_auction_id: u64,
+    // Maps Token Class ID -> Descending price schedule of its primary sale
+    pub dutch_auctions: LookupMap<String, DutchAuction>,
+    // Maps Token Class ID -> When it can be bought
+    pub sale_windows: LookupMap<String, SaleWindow>,
+    // (Sale start, Token Class ID) of classes whose sale window may not have opened yet, ordered by start
+    pub upcoming_drops: TreeMap<(u64, String), ()>,
+    // Maps Token Class ID -> Allowlist phase of its primary sale
+    pub presales: LookupMap<String, Presale>,
+    // (Token Class ID, Account ID) pairs allowed to buy during the presale
+    pub presale_allowlist: LookupSet<(String, AccountId)>,
+    // Maps (Token Class ID, Account ID) -> Copies bought during the presale
+    pub presale_purchases: LookupMap<(String, AccountId), u64>,
+    // Maps Token Class ID -> Holdings a buyer needs before getting copies of it
+    pub purchase_rules: LookupMap<String, Vec<PurchaseRule>>,
+    // Maps Account ID -> NEP-145 storage deposit and the bytes charged against it
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    // Bytes one storage registration takes
+    pub account_storage_usage: u64,
+    // Maps (Owner, Token Class ID) -> Balance as u64
+    pub balances: LookupMap<(AccountId, String), u64>,
+    // Maps Owner -> Token Class IDs they hold copies of
+    pub classes_by_owner: LookupMap<AccountId, IterableSet<String>>,
+    // Maps (Owner, Token Class ID, Approved Account) -> Approved amount
+    pub approvals: LookupMap<(AccountId, String, AccountId), u64>,
+    // Accounts known to still have legacy balance maps, carried over from state that tracked owners
+    pub legacy_owners: UnorderedSet<AccountId>,
+    // Maps (Owner, Token Class ID) -> Account that paid for the balance entry, when not the owner
+    pub balance_storage_payers: LookupMap<(AccountId, String), AccountId>,
+    // Bytes freed during the current call and credited to the accounts that paid for them; not persisted
+    #[borsh(skip)]
+    pub released_storage_usage: u64,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
+    #[allow(deprecated)]
     pub fn new(owner_id: AccountId) -> Self {
-        Self {
+        let mut this = Self {
             owner_id,
             token_metadata: LookupMap::new(StorageKey::TokenMetadata),
             token_supply: LookupMap::new(StorageKey::TokenSupply),
-            owner_balances: LookupMap::new(StorageKey::OwnerBalances),
+            legacy_owner_balances: LookupMap::new(StorageKey::OwnerBalances),
             token_classes: UnorderedSet::new(StorageKey::TokenClasses),
-            approvals: LookupMap::new(StorageKey::Approvals),
+            legacy_approvals: LookupMap::new(StorageKey::Approvals),
             creators: LookupMap::new(StorageKey::CreatorMap),
-        }
+            retired_classes: LookupSet::new(StorageKey::RetiredClasses),
+            max_supply: LookupMap::new(StorageKey::MaxSupply),
+            primary_sale_classes: LookupSet::new(StorageKey::PrimarySaleClasses),
+            minted: LookupMap::new(StorageKey::Minted),
+            class_minters: LookupMap::new(StorageKey::ClassMinters),
+            listings: LookupMap::new(StorageKey::Listings),
+            listings_by_class: LookupMap::new(StorageKey::ListingsByClass),
+            listings_by_seller: LookupMap::new(StorageKey::ListingsBySeller),
+            royalties: LookupMap::new(StorageKey::Royalties),
+            split_sheets: LookupMap::new(StorageKey::SplitSheets),
+            split_proposals: LookupMap::new(StorageKey::SplitProposals),
+            max_payout_recipients: DEFAULT_MAX_PAYOUT_RECIPIENTS,
+            market_fee_bps: 0,
+            treasury_balance: 0,
+            class_fee_totals: LookupMap::new(StorageKey::ClassFeeTotals),
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
+            ft_treasury_balances: LookupMap::new(StorageKey::FtTreasuryBalances),
+            ft_pending_withdrawals: LookupMap::new(StorageKey::FtPendingWithdrawals),
+            accepted_ft_tokens: IterableSet::new(StorageKey::AcceptedFtTokens),
+            offers: LookupMap::new(StorageKey::Offers),
+            offers_by_class: LookupMap::new(StorageKey::OffersByClass),
+            auctions: LookupMap::new(StorageKey::Auctions),
+            auctions_by_class: LookupMap::new(StorageKey::AuctionsByClass),
+            next_auction_id: 0,
+            dutch_auctions: LookupMap::new(StorageKey::DutchAuctions),
+            sale_windows: LookupMap::new(StorageKey::SaleWindows),
+            upcoming_drops: TreeMap::new(StorageKey::UpcomingDrops),
+            presales: LookupMap::new(StorageKey::Presales),
+            presale_allowlist: LookupSet::new(StorageKey::PresaleAllowlist),
+            presale_purchases: LookupMap::new(StorageKey::PresalePurchases),
+            purchase_rules: LookupMap::new(StorageKey::PurchaseRules),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            account_storage_usage: 0,
+            balances: LookupMap::new(StorageKey::Balances),
+            classes_by_owner: LookupMap::new(StorageKey::ClassesByOwner),
+            approvals: LookupMap::new(StorageKey::AccountApprovals),
+            legacy_owners: UnorderedSet::new(StorageKey::OwnerClasses),
+            balance_storage_payers: LookupMap::new(StorageKey::BalanceStoragePayers),
+            released_storage_usage: 0,
+        };
+        this.internal_measure_account_storage_usage();
+        write_state_version();
+        this
     }
 
     /// Mints new SFT copies for a given token class ID.
-    /// If the token class ID doesn't exist, metadata must be provided to create it.
-    /// Only the contract owner can call this.
-    /// Requires deposit to cover storage costs.
+    /// If the token class ID doesn't exist, metadata must be provided to create it,
+    /// optionally with a fixed `max_supply`. Anyone can create a class; only its creator
+    /// and the minters they delegate can mint additional copies of it later.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
     #[payable]
     pub fn sft_mint(&mut self, args: SftMintArgs) {
         // Validate input parameters
@@ -170,7 +373,7 @@ impl Contract {
             // If metadata is provided, the class should NOT exist
             assert!(!class_exists, "Token class ID already exists, cannot provide metadata");
             // Store the new metadata
-            self.token_metadata.insert(args.token_class_id.clone(), meta);
+            self.token_metadata.insert(args.token_class_id.clone(), meta.clone());
             // Initialize total supply
             self.token_supply.insert(args.token_class_id.clone(), 0);
             // Add the new class ID to our set
@@ -179,62 +382,107 @@ impl Contract {
             // --- Store the creator --- 
             let creator_id = env::predecessor_account_id();
             self.creators.insert(args.token_class_id.clone(), creator_id.clone());
-            log!("Created new token class: {} by {}", args.token_class_id, creator_id);
             // --- End Store Creator --- 
 
+            if let Some(max_supply) = args.max_supply {
+                assert!(max_supply.0 > 0, "Max supply must be positive");
+                self.max_supply.insert(args.token_class_id.clone(), max_supply.0);
+            }
+            if let Some(royalty_bps) = args.royalty_bps {
+                assert!(royalty_bps <= MAX_ROYALTY_BPS, "Royalty cannot exceed {} basis points", MAX_ROYALTY_BPS);
+                self.royalties.insert(args.token_class_id.clone(), royalty_bps);
+            }
+            if args.sale_window.is_some() {
+                self.internal_set_sale_window(&args.token_class_id, args.sale_window.clone());
+            }
+            if args.presale.is_some() {
+                self.internal_set_presale(&args.token_class_id, args.presale.clone());
+            }
+            let dutch_auction = args.dutch_auction.map(|mut auction| {
+                assert!(args.max_supply.is_some(), "Max supply is required for a Dutch auction drop");
+                auction.assert_valid();
+                // The price starts dropping when the drop opens
+                let opens_at = args.sale_window.as_ref().and_then(|window| window.starts_at).map_or(0, |starts_at| starts_at.0);
+                auction.starts_at = U64(env::block_timestamp().max(opens_at));
+                self.dutch_auctions.insert(args.token_class_id.clone(), auction.clone());
+                self.primary_sale_classes.insert(args.token_class_id.clone());
+                auction
+            });
+
+            MusicSftEvent::ClassCreate(vec![ClassCreateLog {
+                token_class_id: args.token_class_id.clone(),
+                creator_id,
+                metadata: meta,
+                max_supply: args.max_supply,
+                royalty_bps: args.royalty_bps,
+                dutch_auction,
+                sale_window: args.sale_window,
+                presale: args.presale,
+            }]).emit();
+
         } else {
             // If metadata is not provided, the class MUST exist
             assert!(class_exists, "Token class ID does not exist, metadata must be provided for first mint");
+            assert!(args.max_supply.is_none(), "Max supply can only be set when creating a token class");
+            assert!(args.royalty_bps.is_none(), "Royalty can only be set when creating a token class");
+            assert!(args.dutch_auction.is_none(), "A Dutch auction can only be set when creating a token class");
+            assert!(args.sale_window.is_none(), "Use sft_set_sale_window to change the sale window of an existing class");
+            assert!(args.presale.is_none(), "Use sft_set_presale to change the presale of an existing class");
+            assert!(
+                self.internal_is_minter(&args.token_class_id, &env::predecessor_account_id()),
+                "Only the class creator or a delegated minter can mint additional copies"
+            );
         }
 
-        // 4. Update Total Supply
-        let current_supply = self.token_supply.get(&args.token_class_id).copied().unwrap_or(0);
-        let new_supply = current_supply.checked_add(mint_amount)
-            .expect("Total supply overflow");
-        self.token_supply.insert(args.token_class_id.clone(), new_supply);
+        // 4. Update Total Supply and Receiver Balance
+        self.internal_mint(&args.token_class_id, &args.receiver_id, mint_amount, &env::predecessor_account_id());
 
-        // 5. Update Receiver Balance using standard HashMap
-        let mut receiver_balances = self.owner_balances.remove(&args.receiver_id)
-            .unwrap_or_else(HashMap::new);
-        
-        // Update the balance for this specific token class
-        let current_balance = receiver_balances.entry(args.token_class_id.clone()).or_insert(0);
-        *current_balance = current_balance.checked_add(mint_amount)
-            .expect("Balance overflow");
-
-        // Insert the updated HashMap back into the LookupMap
-        self.owner_balances.insert(args.receiver_id.clone(), receiver_balances);
-
-        // 2. Handle Storage Deposit & Refund
-        let final_storage_usage = env::storage_usage();
-        let storage_used = if final_storage_usage >= initial_storage_usage {
-            final_storage_usage - initial_storage_usage
-        } else {
-            log!("Storage usage decreased unexpectedly!"); // Log potential issue
-            0
-        };
-        
-        let required_deposit = NearToken::from_yoctonear(u128::from(storage_used) * env::storage_byte_cost().as_yoctonear());
-        let attached_deposit = env::attached_deposit();
-        
-        if attached_deposit < required_deposit {
-            env::panic_str(&format!(
-                "Insufficient deposit for storage. Attached: {}, Required: {}",
-                attached_deposit, required_deposit
-            ));
-        }
+        // 2. Charge the caller's storage balance
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Internal helper shared by every mint path: checks the class can still be minted to the receiver,
+    // then updates total supply and the receiver's balance, whose storage `storage_payer` pays for
+    pub(crate) fn internal_mint(&mut self, token_class_id: &str, receiver_id: &AccountId, amount: u64, storage_payer: &AccountId) {
+        assert!(
+            !self.retired_classes.contains(token_class_id),
+            "Token class has been retired and cannot be minted"
+        );
+        self.assert_purchase_rules(token_class_id, receiver_id);
 
-        let refund = attached_deposit.saturating_sub(required_deposit);
-        if refund.as_yoctonear() > 1 { // Don't refund dust
-            Promise::new(env::predecessor_account_id()).transfer(refund);
+        let minted = self.internal_minted(token_class_id).checked_add(amount)
+            .expect("Total supply overflow");
+        if let Some(max_supply) = self.max_supply.get(token_class_id).copied() {
+            if minted > max_supply {
+                env::panic_str(&format!(
+                    "Mint exceeds max supply. Max: {}, Already minted: {}, Requested: {}",
+                    max_supply, minted - amount, amount
+                ));
+            }
         }
+        self.minted.insert(token_class_id.to_string(), minted);
 
-        log!(
-            "Minted {} copies of token class {} for {}",
-            mint_amount,
-            args.token_class_id,
-            args.receiver_id
-        );
+        let current_supply = self.token_supply.get(token_class_id).copied().unwrap_or(0);
+        let new_supply = current_supply.checked_add(amount)
+            .expect("Total supply overflow");
+        self.token_supply.insert(token_class_id.to_string(), new_supply);
+
+        self.internal_deposit(receiver_id, token_class_id, amount, storage_payer);
+
+        MtEvent::MtMint(vec![MtMintLog {
+            owner_id: receiver_id.clone(),
+            token_ids: vec![token_class_id.to_string()],
+            amounts: vec![U128(u128::from(amount))],
+            memo: None,
+        }]).emit();
+    }
+
+    // Internal helper: copies ever minted for a class.
+    // Classes created before minting was tracked fall back to their current supply.
+    fn internal_minted(&self, token_class_id: &str) -> u64 {
+        self.minted.get(token_class_id).copied()
+            .or_else(|| self.token_supply.get(token_class_id).copied())
+            .unwrap_or(0)
     }
 
     // --- View functions --- 
@@ -245,10 +493,21 @@ impl Contract {
 
     // Get balance for an owner and token class
     pub fn sft_balance_of(&self, account_id: AccountId, token_class_id: String) -> U64 {
-        self.owner_balances.get(&account_id)
-            .and_then(|balances| balances.get(&token_class_id).copied()) // Get balance from HashMap
-            .map(U64)
-            .unwrap_or_else(|| U64(0))
+        U64(self.internal_balance_of(&account_id, &token_class_id))
+    }
+
+    // Get minted vs. remaining supply for a token class
+    pub fn sft_supply_info(&self, token_class_id: String) -> Option<SupplyInfo> {
+        let total_supply = self.token_supply.get(&token_class_id).copied()?;
+        let minted = self.internal_minted(&token_class_id);
+        let max_supply = self.max_supply.get(&token_class_id).copied();
+        Some(SupplyInfo {
+            token_class_id,
+            total_supply: U64(total_supply),
+            minted: U64(minted),
+            max_supply: max_supply.map(U64),
+            remaining: max_supply.map(|max_supply| U64(max_supply.saturating_sub(minted))),
+        })
     }
 
      // Get metadata for a token class
@@ -283,62 +542,139 @@ impl Contract {
             .collect()
     }
 
-    /// Returns the SFT inventory for a given owner.
+    /// Returns a page of the SFT inventory for a given owner.
     /// Key: Token Class ID, Value: Balance (U64)
-    pub fn sft_inventory_of_owner(&self, account_id: AccountId) -> HashMap<String, U64> {
-        self.owner_balances.get(&account_id)
-            .map(|balances_map| {
-                // Convert the inner HashMap<String, u64> to HashMap<String, U64>
-                balances_map.iter()
-                    .map(|(class_id, balance)| (class_id.clone(), U64(*balance)))
-                    .collect()
+    pub fn sft_inventory_of_owner(&self, account_id: AccountId, from_index: Option<u64>, limit: Option<u64>) -> HashMap<String, U64> {
+        self.sft_classes_of_owner(account_id.clone(), from_index, limit)
+            .into_iter()
+            .map(|token_class_id| {
+                let balance = self.internal_balance_of(&account_id, &token_class_id);
+                (token_class_id, U64(balance))
             })
-            .unwrap_or_else(HashMap::new) // Return empty map if owner not found
+            .collect()
+    }
+
+    /// Returns a page of the token class IDs an owner holds copies of.
+    pub fn sft_classes_of_owner(&self, account_id: AccountId, from_index: Option<u64>, limit: Option<u64>) -> Vec<String> {
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(u64::MAX);
+
+        // Accounts not migrated yet list their legacy classes first, then the flat ones not among them
+        if let Some(legacy_balances) = self.legacy_owner_balances.get(&account_id) {
+            let mut token_class_ids: Vec<String> = legacy_balances.keys().cloned().collect();
+            token_class_ids.sort();
+            if let Some(flat_class_ids) = self.classes_by_owner.get(&account_id) {
+                token_class_ids.extend(
+                    flat_class_ids.iter().filter(|token_class_id| !legacy_balances.contains_key(*token_class_id)).cloned(),
+                );
+            }
+            return token_class_ids.into_iter()
+                .skip(start_index as usize)
+                .take(limit as usize)
+                .collect();
+        }
+        let Some(token_class_ids) = self.classes_by_owner.get(&account_id) else {
+            return Vec::new();
+        };
+        token_class_ids
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // Get the number of token classes an owner holds copies of
+    pub fn sft_class_count_of_owner(&self, account_id: AccountId) -> u32 {
+        if let Some(legacy_balances) = self.legacy_owner_balances.get(&account_id) {
+            let flat_only = self.classes_by_owner.get(&account_id)
+                .map(|flat_class_ids| {
+                    flat_class_ids.iter().filter(|token_class_id| !legacy_balances.contains_key(*token_class_id)).count()
+                })
+                .unwrap_or(0);
+            return (legacy_balances.len() + flat_only) as u32;
+        }
+        self.classes_by_owner.get(&account_id).map(|token_class_ids| token_class_ids.len()).unwrap_or(0)
     }
 
     // --- Transfer and Approval Logic --- 
 
-    // Internal helper for balance updates
-    fn internal_transfer(
+    // Internal helper for balance reads
+    fn internal_balance_of(&self, account_id: &AccountId, token_class_id: &str) -> u64 {
+        let balance = self.balances.get(&(account_id.clone(), token_class_id.to_string())).copied().unwrap_or(0);
+        // A class not migrated yet keeps the copies held before the migration in the legacy map
+        let legacy_balance = self.legacy_owner_balances.get(account_id)
+            .and_then(|balances| balances.get(token_class_id).copied())
+            .unwrap_or(0);
+        balance + legacy_balance
+    }
+
+    // Internal helper: removes copies from an account's balance.
+    // `storage_payer` pays for moving the class out of a legacy map first, if it is still there.
+    pub(crate) fn internal_withdraw(&mut self, account_id: &AccountId, token_class_id: &str, amount: u64, storage_payer: &AccountId) {
+        self.internal_migrate_legacy_classes(account_id, Some(token_class_id), 1, storage_payer);
+        let key = (account_id.clone(), token_class_id.to_string());
+        let balance = self.balances.get(&key).copied()
+            .expect("Sender does not own this token class");
+        assert!(balance >= amount, "Insufficient balance for transfer");
+
+        // If balance becomes zero, remove the entry and drop the class from the owner's index.
+        // The freed storage goes back to whoever paid for the entry.
+        if balance == amount {
+            let entry_payer = self.balance_storage_payers.get(&key).cloned().unwrap_or_else(|| account_id.clone());
+            self.internal_remove_paid_by(Some(&entry_payer), |this| {
+                this.balances.remove(&key);
+                this.balance_storage_payers.remove(&key);
+                if let Some(token_class_ids) = this.classes_by_owner.get_mut(account_id) {
+                    token_class_ids.remove(token_class_id);
+                    token_class_ids.flush();
+                    if token_class_ids.is_empty() {
+                        this.classes_by_owner.remove(account_id);
+                    }
+                }
+            });
+        } else {
+            self.balances.insert(key, balance - amount);
+        }
+    }
+
+    // Internal helper: adds copies to an account's balance. A new entry is paid for by `storage_payer`,
+    // recorded when it is not the owner so the storage goes back to them once the entry is emptied.
+    // Leaves a legacy receiver's legacy maps alone, so senders never pay to migrate them.
+    pub(crate) fn internal_deposit(&mut self, account_id: &AccountId, token_class_id: &str, amount: u64, storage_payer: &AccountId) {
+        let key = (account_id.clone(), token_class_id.to_string());
+        let balance = self.balances.get(&key).copied().unwrap_or(0);
+        let new_balance = balance.checked_add(amount)
+            .expect("Receiver balance overflow");
+        self.balances.insert(key.clone(), new_balance);
+
+        if balance == 0 {
+            if storage_payer != account_id {
+                self.balance_storage_payers.insert(key, storage_payer.clone());
+            }
+            let token_class_ids = self.classes_by_owner
+                .entry(account_id.clone())
+                .or_insert_with(|| {
+                    IterableSet::new(StorageKey::ClassesByOwnerInner {
+                        account_hash: env::sha256_array(account_id.as_bytes()),
+                    })
+                });
+            token_class_ids.insert(token_class_id.to_string());
+            token_class_ids.flush();
+        }
+    }
+
+    // Internal helper for balance updates; `storage_payer` is the account the call charges for storage
+    pub(crate) fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
         receiver_id: &AccountId,
         token_class_id: &str,
         amount: u64,
+        storage_payer: &AccountId,
     ) {
-        // Get sender's balances, panic if sender doesn't exist (shouldn't happen if balance > 0)
-        let mut sender_balances = self.owner_balances.remove(sender_id)
-            .expect("Sender not found in owner_balances");
-        
-        // Get sender's balance for this token class, panic if insufficient
-        let sender_balance = sender_balances.get_mut(token_class_id)
-            .expect("Sender does not own this token class");
-        
-        assert!(*sender_balance >= amount, "Insufficient balance for transfer");
-        *sender_balance -= amount;
-
-        // If balance becomes zero, remove the entry for this token class
-        let sender_balance_after = *sender_balance;
-        if sender_balance_after == 0 {
-            sender_balances.remove(token_class_id);
-        }
-
-        // If the sender's balance map is now empty, do not re-insert
-        if !sender_balances.is_empty() {
-            // Re-insert the modified sender's balance map (no clone needed, it's owned)
-            self.owner_balances.insert(sender_id.clone(), sender_balances);
-        }
-
-        // Update receiver's balances (already uses remove/insert correctly)
-        let mut receiver_balances = self.owner_balances.remove(receiver_id)
-            .unwrap_or_else(HashMap::new);
-        
-        let receiver_balance = receiver_balances.entry(token_class_id.to_string()).or_insert(0);
-        *receiver_balance = receiver_balance.checked_add(amount)
-            .expect("Receiver balance overflow");
-        
-        // Insert the updated receiver balances back into the LookupMap (no clone needed)
-        self.owner_balances.insert(receiver_id.clone(), receiver_balances);
+        self.internal_withdraw(sender_id, token_class_id, amount, storage_payer);
+        self.internal_deposit(receiver_id, token_class_id, amount, storage_payer);
     }
 
     /// Transfers SFT copies from the predecessor account to a receiver.
@@ -361,21 +697,53 @@ impl Contract {
 
         assert!(amount > 0, "Transfer amount must be positive");
         assert_ne!(sender_id, receiver_id, "Sender and receiver cannot be the same");
+        let initial_storage_usage = env::storage_usage();
 
-        self.internal_transfer(&sender_id, &receiver_id, &token_class_id, amount);
-
-        // Log NEP-297 event
-        log_nep297_event(
-            "sft_transfer", 
-            SftTransferLogData {
-                owner_id: sender_id, // Sender is the owner in this case
-                receiver_id, 
-                token_class_id, 
-                amount: U64(amount), 
-                memo, 
-                authorized_id: None 
-            }
-        );
+        self.internal_transfer(&sender_id, &receiver_id, &token_class_id, amount, &sender_id);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: None,
+            old_owner_id: sender_id, // Sender is the owner in this case
+            new_owner_id: receiver_id,
+            token_ids: vec![token_class_id],
+            amounts: vec![U128(u128::from(amount))],
+            memo,
+        }]).emit();
+    }
+
+    /// Transfers copies of several token classes from the predecessor to one receiver.
+    /// `token_class_ids` and `amounts` are parallel lists; either every pair moves or none does.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_class_ids: Vec<String>,
+        amounts: Vec<U64>,
+        memo: Option<String>
+    ) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert_ne!(sender_id, receiver_id, "Sender and receiver cannot be the same");
+        let amounts = assert_batch_args(&token_class_ids, &amounts);
+        let initial_storage_usage = env::storage_usage();
+
+        for (token_class_id, amount) in token_class_ids.iter().zip(&amounts) {
+            self.internal_transfer(&sender_id, &receiver_id, token_class_id, *amount, &sender_id);
+        }
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: None,
+            old_owner_id: sender_id,
+            new_owner_id: receiver_id,
+            token_ids: token_class_ids,
+            amounts: amounts.into_iter().map(|amount| U128(u128::from(amount))).collect(),
+            memo,
+        }]).emit();
     }
 
     /// Approves an account to transfer specific SFT copies on behalf of the owner.
@@ -390,64 +758,30 @@ impl Contract {
         let approval_amount: u64 = amount.0; // Use a different name to avoid shadowing
 
         assert_ne!(owner_id, account_id_to_approve, "Owner cannot approve themselves");
+        assert_ne!(
+            account_id_to_approve,
+            env::current_account_id(),
+            "The marketplace does not use approvals, list copies with market_list_sft instead"
+        );
 
         // Measure initial storage
         let initial_storage_usage = env::storage_usage();
 
-        // Get or create the owner's approval map
-        let mut owner_approvals = self.approvals.remove(&owner_id)
-            .unwrap_or_else(HashMap::new);
-        
-        // Get or create the token class map within the owner's map
-        let class_approvals = owner_approvals.entry(token_class_id.clone()).or_insert_with(HashMap::new);
-        
-        // Insert or update the approval for the specific account and amount
-        class_approvals.insert(account_id_to_approve.clone(), approval_amount);
-
-        // Re-insert the updated owner approvals map
-        self.approvals.insert(owner_id.clone(), owner_approvals);
-
-        // Handle storage deposit refund
-        let final_storage_usage = env::storage_usage();
-        let storage_used = if final_storage_usage >= initial_storage_usage {
-            final_storage_usage - initial_storage_usage
-        } else {
-            // This case might happen if an existing approval for 0 is overwritten
-            // or if storage usage calculation has quirks. We assume 0 cost if usage decreases.
-            0 
-        };
+        self.internal_migrate_legacy_classes(&owner_id, Some(&token_class_id), 1, &owner_id);
+        self.approvals.insert(
+            (owner_id.clone(), token_class_id.clone(), account_id_to_approve.clone()),
+            approval_amount,
+        );
 
-        let required_deposit = NearToken::from_yoctonear(u128::from(storage_used) * env::storage_byte_cost().as_yoctonear());
-        let attached_deposit = env::attached_deposit();
+        // Charge the owner's storage balance
+        self.internal_charge_predecessor_storage(initial_storage_usage);
 
-        // Refund only if attached deposit is greater than required deposit
-        // Allows attaching 0 if no new storage is needed (e.g., updating existing approval)
-        if attached_deposit > required_deposit {
-            let refund = attached_deposit.saturating_sub(required_deposit);
-            if refund.as_yoctonear() > 1 { // Don't refund dust
-                Promise::new(owner_id.clone()).transfer(refund);
-            }
-        } else if attached_deposit < required_deposit {
-            // Panic if attached deposit is less than required, unless required is 0
-            if required_deposit.as_yoctonear() > 0 {
-                 env::panic_str(&format!(
-                    "Insufficient deposit for storage. Attached: {}, Required: {}",
-                    attached_deposit, required_deposit
-                ));
-            }
-        }
-        // If attached_deposit == required_deposit, do nothing (no refund needed)
-
-        // Log NEP-297 event
-         log_nep297_event(
-            "sft_approve",
-            SftApproveLogData {
-                owner_id, 
-                approved_account_id: account_id_to_approve, 
-                token_class_id, 
-                amount: U64(approval_amount)
-            }
-        );
+        MusicSftEvent::Approve(vec![ApproveLog {
+            owner_id,
+            approved_account_id: account_id_to_approve,
+            token_class_id,
+            amount: U128(u128::from(approval_amount)),
+        }]).emit();
     }
 
     /// Revokes an approval for a specific account and token class.
@@ -463,41 +797,20 @@ impl Contract {
             "Requires attached deposit of exactly 1 yoctoNEAR"
         );
         let owner_id = env::predecessor_account_id();
-        let mut revoked_event_fired = false; 
-
-        // Use remove/insert pattern for mutability
-        if let Some(mut owner_approvals) = self.approvals.remove(&owner_id) {
-            // Mutably access class approvals within the owned owner_approvals
-             if let Some(class_approvals) = owner_approvals.get_mut(&token_class_id) {
-                // Remove the specific account being revoked
-                if class_approvals.remove(&account_id_to_revoke).is_some() {
-                    revoked_event_fired = true;
-                     // If the class map is now empty, remove it from the owner's map
-                    if class_approvals.is_empty() {
-                        owner_approvals.remove(&token_class_id);
-                    }
-                    // Note: No need to re-insert class_approvals explicitly as we modified it via get_mut
-                }
-            }
-            // If the owner's map is now empty, don't re-insert it
-            if !owner_approvals.is_empty() {
-                // Re-insert the potentially modified owner approvals map
-                self.approvals.insert(owner_id.clone(), owner_approvals);
-            }
+        let initial_storage_usage = env::storage_usage();
 
-            if revoked_event_fired {
-                 // Log NEP-297 event only if something was actually revoked
-                 log_nep297_event(
-                     "sft_revoke",
-                     SftRevokeLogData {
-                         owner_id: owner_id.clone(), 
-                         revoked_account_id: account_id_to_revoke.clone(), 
-                         token_class_id: token_class_id.clone()
-                     }
-                 );
-            }
-        } 
-        // If owner_id not found in self.approvals, do nothing.
+        self.internal_migrate_legacy_classes(&owner_id, Some(&token_class_id), 1, &owner_id);
+        let key = (owner_id.clone(), token_class_id.clone(), account_id_to_revoke.clone());
+        if self.approvals.remove(&key).is_some() {
+            // Log event only if something was actually revoked
+            MusicSftEvent::Revoke(vec![RevokeLog {
+                owner_id,
+                revoked_account_id: account_id_to_revoke,
+                token_class_id,
+            }]).emit();
+        }
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
     }
 
     /// Checks the approved amount for a specific account and token class.
@@ -507,36 +820,61 @@ impl Contract {
         approved_account_id: AccountId,
         token_class_id: String
     ) -> U64 {
-        self.approvals.get(&owner_id)
-            .and_then(|owner_approvals| owner_approvals.get(&token_class_id))
-            .and_then(|class_approvals| class_approvals.get(&approved_account_id).copied())
-            .map(U64)
-            .unwrap_or_else(|| U64(0))
+        U64(self.internal_approved_amount(&owner_id, &token_class_id, &approved_account_id))
     }
 
-    /// Returns a map of sellers who have approved the marketplace contract for a given token class
-    /// among a provided list of potential sellers.
-    /// Key: Seller Account ID, Value: Approved amount (U64)
-    pub fn get_market_approved_sellers(&self, token_class_id: String, seller_ids: Vec<AccountId>) -> HashMap<AccountId, U64> {
-        let marketplace_id = env::current_account_id();
-        let mut approved_sellers = HashMap::new();
-
-        // Iterate through provided seller IDs
-        for owner_id in seller_ids {
-            // Get the owner's full approval map
-            if let Some(owner_approvals) = self.approvals.get(&owner_id) { 
-                // Check for the specific token class
-                if let Some(class_approvals) = owner_approvals.get(&token_class_id) {
-                    // Check if the marketplace is approved
-                    if let Some(approved_amount) = class_approvals.get(&marketplace_id).copied() {
-                        if approved_amount > 0 {
-                             approved_sellers.insert(owner_id.clone(), U64(approved_amount));
-                        }
-                    }
-                }
-            }
+    // Internal helper for approval reads
+    fn internal_approved_amount(&self, owner_id: &AccountId, token_class_id: &str, approved_account_id: &AccountId) -> u64 {
+        // Approvals of the contract itself belong to the old approval-based market and are void
+        if *approved_account_id == env::current_account_id() {
+            return 0;
+        }
+        let key = (owner_id.clone(), token_class_id.to_string(), approved_account_id.clone());
+        if let Some(approved_amount) = self.approvals.get(&key) {
+            return *approved_amount;
+        }
+        // Accounts not migrated yet fall back to their legacy map
+        self.legacy_approvals.get(owner_id)
+            .and_then(|owner_approvals| owner_approvals.get(token_class_id))
+            .and_then(|class_approvals| class_approvals.get(approved_account_id).copied())
+            .unwrap_or(0)
+    }
+
+    // Internal helper: decrements an approved amount, panicking if it is insufficient
+    fn internal_use_approval(
+        &mut self,
+        owner_id: &AccountId,
+        approved_account_id: &AccountId,
+        token_class_id: &str,
+        amount: u64,
+    ) {
+        self.internal_migrate_legacy_classes(owner_id, Some(token_class_id), 1, approved_account_id);
+        let key = (owner_id.clone(), token_class_id.to_string(), approved_account_id.clone());
+        let approved_amount = self.approvals.get(&key).copied()
+            .expect("Predecessor account is not approved for this token class");
+        assert!(approved_amount >= amount, "Approved amount is insufficient for this transfer");
+
+        // If the approved amount becomes zero, remove the approval entry
+        if approved_amount == amount {
+            self.approvals.remove(&key);
+        } else {
+            self.approvals.insert(key, approved_amount - amount);
         }
-        approved_sellers
+    }
+
+    // Internal helper: gives back an allowance consumed by a transfer that was later refunded
+    fn internal_restore_approval(
+        &mut self,
+        owner_id: &AccountId,
+        approved_account_id: &AccountId,
+        token_class_id: &str,
+        amount: u64,
+    ) -> u64 {
+        self.internal_migrate_legacy_classes(owner_id, Some(token_class_id), 1, approved_account_id);
+        let key = (owner_id.clone(), token_class_id.to_string(), approved_account_id.clone());
+        let approved_amount = self.approvals.get(&key).copied().unwrap_or(0).saturating_add(amount);
+        self.approvals.insert(key, approved_amount);
+        approved_amount
     }
 
     /// Transfers SFT copies from a specified owner account by an approved account (predecessor).
@@ -562,156 +900,164 @@ impl Contract {
         assert_ne!(owner_id, receiver_id, "Owner and receiver cannot be the same");
         assert_ne!(approved_sender_id, receiver_id, "Approved sender and receiver cannot be the same");
         assert_ne!(owner_id, approved_sender_id, "Owner cannot transfer from themselves using this method");
+        let initial_storage_usage = env::storage_usage();
 
-        // Use remove/insert pattern for mutability
-        let mut owner_approvals = self.approvals.remove(&owner_id)
-            .expect("Owner has no approvals");
-
-        // Get the class-specific approvals map mutably
-        let class_approvals = owner_approvals.get_mut(&token_class_id)
-             .expect("Owner has no approvals for this token class");
-        
-        // Get the approved amount for the predecessor mutably
-        let approved_amount = class_approvals.get_mut(&approved_sender_id)
-            .expect("Predecessor account is not approved for this token class");
-        
-        assert!(*approved_amount >= transfer_amount, "Approved amount is insufficient for this transfer");
-        *approved_amount -= transfer_amount;
-        let approved_amount_after = *approved_amount; // Capture value after borrow ends
-
-        // If the approved amount becomes zero, remove the approval entry
-        if approved_amount_after == 0 {
-            class_approvals.remove(&approved_sender_id);
-        }
-        // Cleanup class map if empty
-        let class_map_empty = class_approvals.is_empty();
-        if class_map_empty {
-             owner_approvals.remove(&token_class_id);
-        }
-        
-        // Re-insert owner approvals map if not empty
-        if !owner_approvals.is_empty() {
-            self.approvals.insert(owner_id.clone(), owner_approvals);
-        }
+        // Consume the predecessor's allowance
+        self.internal_use_approval(&owner_id, &approved_sender_id, &token_class_id, transfer_amount);
 
         // Perform the actual token transfer using the internal helper
-        self.internal_transfer(&owner_id, &receiver_id, &token_class_id, transfer_amount);
-
-        // Log NEP-297 event
-        log_nep297_event(
-            "sft_transfer", // Standard event name for transfers
-            SftTransferLogData {
-                owner_id: owner_id.clone(), 
-                receiver_id: receiver_id.clone(), 
-                token_class_id: token_class_id.clone(), 
-                amount: U64(transfer_amount), 
-                memo: memo.clone(), 
-                authorized_id: Some(approved_sender_id) // Indicate who initiated the transfer
-            }
-        );
+        self.internal_transfer(&owner_id, &receiver_id, &token_class_id, transfer_amount, &approved_sender_id);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: Some(approved_sender_id), // Indicate who initiated the transfer
+            old_owner_id: owner_id,
+            new_owner_id: receiver_id,
+            token_ids: vec![token_class_id],
+            amounts: vec![U128(u128::from(transfer_amount))],
+            memo,
+        }]).emit();
     }
 
-    // --- Marketplace Logic --- 
-
-    /// Allows a buyer (predecessor) to purchase 1 copy of an SFT from a specific seller.
-    /// Requires seller to have approved the marketplace contract (`env::current_account_id()`).
-    /// Requires buyer to attach enough NEAR to cover the price.
+    /// Batch variant of `sft_transfer_from`: moves several token classes from `owner_id`,
+    /// consuming the predecessor's approval for each class.
+    /// Requires exactly 1 yoctoNEAR attached for security.
     #[payable]
-    pub fn market_buy_sft(
+    pub fn sft_batch_transfer_from(
         &mut self,
-        token_class_id: String,
-        seller_id: AccountId
+        owner_id: AccountId,
+        receiver_id: AccountId,
+        token_class_ids: Vec<String>,
+        amounts: Vec<U64>,
+        memo: Option<String>
     ) {
-        let buyer_id = env::predecessor_account_id();
-        let marketplace_id = env::current_account_id();
-        let amount_to_buy: u64 = 1; // Hardcoded to buy 1 copy for now
-
-        assert_ne!(buyer_id, seller_id, "Buyer and seller cannot be the same");
-
-        // --- Get Metadata & Price ---
-        let metadata = self.token_metadata.get(&token_class_id)
-            .expect("Token class metadata not found");
-        let price_per_copy = metadata.price_per_copy.0; // u128
-
-        // --- Verify Deposit ---
-        let attached_deposit = env::attached_deposit().as_yoctonear(); // u128
-        if attached_deposit < price_per_copy {
-            env::panic_str(&format!(
-                "Insufficient deposit. Attached: {}, Required: {}", attached_deposit, price_per_copy
-            ));
-        }
-        // assert!(attached_deposit >= price_per_copy, 
-        //     &format!("Insufficient deposit. Attached: {}, Required: {}", attached_deposit, price_per_copy)
-        // );
+        assert_one_yocto();
+        let approved_sender_id = env::predecessor_account_id();
+        assert_ne!(owner_id, receiver_id, "Owner and receiver cannot be the same");
+        assert_ne!(approved_sender_id, receiver_id, "Approved sender and receiver cannot be the same");
+        assert_ne!(owner_id, approved_sender_id, "Owner cannot transfer from themselves using this method");
+        let amounts = assert_batch_args(&token_class_ids, &amounts);
+        let initial_storage_usage = env::storage_usage();
 
-        // --- Verify & Decrement Approval ---
-        // Get seller's approvals
-        // Clone owner_approvals immediately after getting it
-        let mut owner_approvals = self.approvals.get(&seller_id)
-            .expect("Seller has no approvals recorded").clone();
+        for (token_class_id, amount) in token_class_ids.iter().zip(&amounts) {
+            self.internal_use_approval(&owner_id, &approved_sender_id, token_class_id, *amount);
+            self.internal_transfer(&owner_id, &receiver_id, token_class_id, *amount, &approved_sender_id);
+        }
 
-        // Get class-specific approvals (mutable borrow needed for update)
-        // No need to clone class_approvals here as it's removed from owner_approvals
-        let mut class_approvals = owner_approvals.remove(&token_class_id)
-             .expect("Seller has no approvals for this token class"); 
+        self.internal_charge_predecessor_storage(initial_storage_usage);
 
-        // Get the marketplace's approved amount (mutable borrow needed for update)
-        let approved_amount = class_approvals.get_mut(&marketplace_id)
-            .expect("Marketplace contract is not approved by the seller for this token class");
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: Some(approved_sender_id),
+            old_owner_id: owner_id,
+            new_owner_id: receiver_id,
+            token_ids: token_class_ids,
+            amounts: amounts.into_iter().map(|amount| U128(u128::from(amount))).collect(),
+            memo,
+        }]).emit();
+    }
 
-        // assert!(*approved_amount >= amount_to_buy, 
-        //     &format!("Seller has not approved enough copies for sale. Approved: {}, Required: {}", *approved_amount, amount_to_buy)
-        // );
-        if *approved_amount < amount_to_buy {
-            env::panic_str(&format!(
-                "Seller has not approved enough copies for sale. Approved: {}, Required: {}", *approved_amount, amount_to_buy
-            ));
-        }
-        
-        *approved_amount -= amount_to_buy;
-        let approved_amount_after = *approved_amount; // Capture value after borrow
+    /// Transfers SFT copies to a contract and calls `sft_on_transfer` on it.
+    /// Copies the receiver reports as unused are returned in `sft_resolve_transfer`.
+    /// Pass `owner_id` to send another account's copies using an approval.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        owner_id: Option<AccountId>,
+        memo: Option<String>,
+        msg: String
+    ) -> PromiseOrValue<U64> {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let transfer_amount: u64 = amount.0;
 
-        // Cleanup approvals map if amount becomes 0
-        if approved_amount_after == 0 {
-            class_approvals.remove(&marketplace_id);
-        }
+        assert!(transfer_amount > 0, "Transfer amount must be positive");
+        assert_transfer_call_gas(GAS_FOR_SFT_TRANSFER_CALL);
+        let previous_owner_id = owner_id.unwrap_or_else(|| sender_id.clone());
+        assert_ne!(previous_owner_id, receiver_id, "Sender and receiver cannot be the same");
+        let initial_storage_usage = env::storage_usage();
 
-        // Re-insert the modified approval maps (handling empty maps)
-        if !class_approvals.is_empty() {
-            owner_approvals.insert(token_class_id.clone(), class_approvals);
-        }
-        if owner_approvals.is_empty() {
-            self.approvals.remove(&seller_id);
+        // Consume the allowance when moving someone else's copies, so it can be restored on refund
+        let approved_account_id = if previous_owner_id != sender_id {
+            self.internal_use_approval(&previous_owner_id, &sender_id, &token_class_id, transfer_amount);
+            Some(sender_id.clone())
         } else {
-            // owner_approvals is already owned (cloned earlier), no further clone needed
-            self.approvals.insert(seller_id.clone(), owner_approvals);
-        }
+            None
+        };
 
-        // --- Transfer NEAR to Seller ---
-        Promise::new(seller_id.clone()).transfer(NearToken::from_yoctonear(price_per_copy));
+        self.internal_transfer(&previous_owner_id, &receiver_id, &token_class_id, transfer_amount, &sender_id);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: approved_account_id.clone(),
+            old_owner_id: previous_owner_id.clone(),
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![token_class_id.clone()],
+            amounts: vec![U128(u128::from(transfer_amount))],
+            memo,
+        }]).emit();
+
+        // The receiver gets what is left once this call and the resolve callback are provided for
+        let receiver_gas = env::prepaid_gas()
+            .saturating_sub(env::used_gas())
+            .saturating_sub(GAS_FOR_SFT_TRANSFER_CALL)
+            .saturating_sub(GAS_FOR_RESOLVE_TRANSFER);
+
+        ext_sft_receiver::ext(receiver_id.clone())
+            .with_static_gas(receiver_gas)
+            .sft_on_transfer(sender_id, previous_owner_id.clone(), token_class_id.clone(), amount, msg)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_TRANSFER)
+                    .sft_resolve_transfer(previous_owner_id, receiver_id, token_class_id, amount, approved_account_id)
+            )
+            .into()
+    }
 
-        // --- Transfer SFT Copy to Buyer ---
-        self.internal_transfer(&seller_id, &buyer_id, &token_class_id, amount_to_buy);
+    /// Callback after `sft_on_transfer`: returns unused copies to the previous owner
+    /// and restores the allowance consumed for them. The account that sent the copies pays for the storage.
+    /// Returns the amount that was actually transferred.
+    #[private]
+    pub fn sft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        approved_account_id: Option<AccountId>
+    ) -> U64 {
+        let initial_storage_usage = env::storage_usage();
+        // If the receiver failed or returned garbage, every copy is treated as unused
+        let unused = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U64>(&value)
+                .map(|unused| unused.0.min(amount.0))
+                .unwrap_or(amount.0),
+            PromiseResult::Failed => amount.0,
+        };
 
-        // --- Refund Excess Deposit ---
-        let refund = attached_deposit.saturating_sub(price_per_copy);
-        if refund > 0 { 
-            Promise::new(buyer_id.clone()).transfer(NearToken::from_yoctonear(refund)); 
+        let sender_id = approved_account_id.as_ref().unwrap_or(&previous_owner_id);
+        let (refund, refund_log, approve_logs) = self.internal_refund_unused(
+            &previous_owner_id,
+            &receiver_id,
+            &token_class_id,
+            unused,
+            approved_account_id.as_slice(),
+            sender_id,
+        );
+        if let Some(refund_log) = refund_log {
+            MtEvent::MtTransfer(vec![refund_log]).emit();
+        }
+        if !approve_logs.is_empty() {
+            MusicSftEvent::Approve(approve_logs).emit();
         }
 
-        // --- Logging ---
-        // Reuse sft_transfer event, indicating marketplace involvement via authorized_id
-        log_nep297_event(
-            "sft_transfer",
-            SftTransferLogData {
-                owner_id: seller_id, // The original owner
-                receiver_id: buyer_id, // The buyer
-                token_class_id, 
-                amount: U64(amount_to_buy), 
-                memo: Some("Marketplace purchase".to_string()), 
-                authorized_id: Some(marketplace_id) // Indicates marketplace facilitated
-            }
-        );
+        self.internal_charge_storage_owed(sender_id, initial_storage_usage, 0);
+        U64(amount.0 - refund)
     }
 
     // --- Old functions (Commented out or removed as they are incompatible) --- 
@@ -0,0 +1,301 @@
+// --- Marketplace Logic ---
+// Every seller keeps at most one listing per token class, with their own price,
+// quantity and optional expiry. Listings are indexed per class and per seller for paging.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::store::IterableSet;
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Listing {
+    pub seller_id: AccountId,
+    pub token_class_id: String,
+    pub price_per_copy: U128,
+    pub quantity: U64, // Copies still for sale
+    pub expires_at: Option<U64>, // Block timestamp in nanoseconds
+    pub ft_token_id: Option<AccountId>, // NEP-141 token the price is in, NEAR when unset
+}
+
+impl Listing {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: reports the current state of a listing, quantity 0 when it is gone
+    fn internal_emit_listing_update(&self, listing: &Listing) {
+        MusicSftEvent::ListingUpdate(vec![ListingUpdateLog {
+            seller_id: listing.seller_id.clone(),
+            token_class_id: listing.token_class_id.clone(),
+            amount: U128(u128::from(listing.quantity.0)),
+            price_per_copy: listing.price_per_copy,
+            expires_at: listing.expires_at,
+            ft_token_id: listing.ft_token_id.clone(),
+        }]).emit();
+    }
+
+    // Internal helper: stores a listing and adds it to both indexes
+    fn internal_add_listing(&mut self, listing: Listing) {
+        let seller_id = listing.seller_id.clone();
+        let token_class_id = listing.token_class_id.clone();
+
+        let sellers = self.listings_by_class
+            .entry(token_class_id.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::ListingsByClassInner {
+                    token_class_hash: env::sha256_array(token_class_id.as_bytes()),
+                })
+            });
+        sellers.insert(seller_id.clone());
+        sellers.flush(); // Nested sets cache writes; settle them before storage is measured
+        let class_ids = self.listings_by_seller
+            .entry(seller_id.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::ListingsBySellerInner {
+                    account_hash: env::sha256_array(seller_id.as_bytes()),
+                })
+            });
+        class_ids.insert(token_class_id.clone());
+        class_ids.flush();
+        self.listings.insert((seller_id, token_class_id), listing);
+    }
+
+    // Internal helper: removes a listing and drops it from both indexes
+    fn internal_remove_listing(&mut self, seller_id: &AccountId, token_class_id: &str) -> Listing {
+        let listing = self.listings.remove(&(seller_id.clone(), token_class_id.to_string()))
+            .expect("Listing not found");
+
+        if let Some(sellers) = self.listings_by_class.get_mut(token_class_id) {
+            sellers.remove(seller_id);
+            sellers.flush();
+            if sellers.is_empty() {
+                self.listings_by_class.remove(token_class_id);
+            }
+        }
+        if let Some(class_ids) = self.listings_by_seller.get_mut(seller_id) {
+            class_ids.remove(token_class_id);
+            class_ids.flush();
+            if class_ids.is_empty() {
+                self.listings_by_seller.remove(seller_id);
+            }
+        }
+        listing
+    }
+
+    // Internal helper: takes copies off a listing paid in `ft_token_id` (NEAR when None) and moves them
+    // from the seller to the buyer. Returns the total price.
+    pub(crate) fn internal_fill_listing(
+        &mut self,
+        buyer_id: &AccountId,
+        seller_id: &AccountId,
+        token_class_id: &str,
+        amount_to_buy: u64,
+        ft_token_id: Option<&AccountId>,
+    ) -> u128 {
+        assert!(amount_to_buy > 0, "Purchase quantity must be positive");
+        assert_ne!(buyer_id, seller_id, "Buyer and seller cannot be the same");
+        self.assert_released(token_class_id);
+        self.assert_purchase_rules(token_class_id, buyer_id);
+
+        // --- Get Listing & Price ---
+        let listing_key = (seller_id.clone(), token_class_id.to_string());
+        let mut listing = self.listings.get(&listing_key)
+            .expect("Listing not found")
+            .clone();
+        assert!(!listing.is_expired(), "Listing has expired");
+        match (listing.ft_token_id.as_ref(), ft_token_id) {
+            (None, None) => {}
+            (Some(listed), Some(paid)) if listed == paid => {}
+            (Some(listed), _) => env::panic_str(&format!("Listing is priced in {}", listed)),
+            (None, Some(_)) => env::panic_str("Listing is priced in NEAR"),
+        }
+        if listing.quantity.0 < amount_to_buy {
+            env::panic_str(&format!(
+                "Listing does not have enough copies for sale. Listed: {}, Required: {}", listing.quantity.0, amount_to_buy
+            ));
+        }
+        let total_price = listing.price_per_copy.0.checked_mul(u128::from(amount_to_buy))
+            .expect("Price overflow"); // u128
+
+        // --- Decrement Listing ---
+        listing.quantity = U64(listing.quantity.0 - amount_to_buy);
+        if listing.quantity.0 == 0 {
+            // The seller paid for the listing, so they get its storage back
+            self.internal_remove_paid_by(Some(seller_id), |this| this.internal_remove_listing(seller_id, token_class_id));
+        } else {
+            self.listings.insert(listing_key, listing.clone());
+        }
+        self.internal_emit_listing_update(&listing);
+
+        // --- Transfer SFT Copies to Buyer ---
+        self.internal_transfer(seller_id, buyer_id, token_class_id, amount_to_buy, buyer_id);
+        // Transfer event indicates marketplace involvement via authorized_id
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: Some(env::current_account_id()), // Indicates marketplace facilitated
+            old_owner_id: seller_id.clone(), // The original owner
+            new_owner_id: buyer_id.clone(), // The buyer
+            token_ids: vec![token_class_id.to_string()],
+            amounts: vec![U128(u128::from(amount_to_buy))],
+            memo: Some("Marketplace purchase".to_string()),
+        }]).emit();
+
+        total_price
+    }
+
+    /// Lists copies of a token class for sale at the seller's own price, replacing any earlier listing.
+    /// `expires_at` is a block timestamp in nanoseconds after which the listing can no longer be bought.
+    /// `ft_token_id` prices the listing in a NEP-141 token instead of NEAR; it is bought via `ft_transfer_call`
+    /// and must be one of the tokens the owner accepts.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn market_list_sft(
+        &mut self,
+        token_class_id: String,
+        price_per_copy: U128,
+        quantity: U64,
+        expires_at: Option<U64>,
+        ft_token_id: Option<AccountId>
+    ) {
+        let seller_id = env::predecessor_account_id();
+        assert!(quantity.0 > 0, "Listing quantity must be positive");
+        assert!(
+            self.internal_balance_of(&seller_id, &token_class_id) >= quantity.0,
+            "Seller does not own enough copies to list"
+        );
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Listing expiry must be in the future");
+        }
+        if let Some(ft_token_id) = ft_token_id.as_ref() {
+            self.assert_accepted_ft_token(ft_token_id);
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        if self.listings.contains_key(&(seller_id.clone(), token_class_id.clone())) {
+            self.internal_remove_listing(&seller_id, &token_class_id);
+        }
+        let listing = Listing {
+            seller_id,
+            token_class_id,
+            price_per_copy,
+            quantity,
+            expires_at,
+            ft_token_id,
+        };
+        self.internal_emit_listing_update(&listing);
+        self.internal_add_listing(listing);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Removes the predecessor's listing for a token class.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn market_delist_sft(&mut self, token_class_id: String) {
+        assert_one_yocto();
+        let seller_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let mut listing = self.internal_remove_listing(&seller_id, &token_class_id);
+        listing.quantity = U64(0);
+        self.internal_emit_listing_update(&listing);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Allows a buyer (predecessor) to purchase `quantity` copies (default 1) from a seller's listing
+    /// at the listed price. Requires buyer to attach enough NEAR to cover the total price.
+    /// Listings priced in a fungible token are bought through `ft_transfer_call` instead.
+    #[payable]
+    pub fn market_buy_sft(
+        &mut self,
+        token_class_id: String,
+        seller_id: AccountId,
+        quantity: Option<U64>
+    ) {
+        let buyer_id = env::predecessor_account_id();
+        let amount_to_buy: u64 = quantity.map(|quantity| quantity.0).unwrap_or(1);
+        let initial_storage_usage = env::storage_usage();
+        let total_price = self.internal_fill_listing(&buyer_id, &seller_id, &token_class_id, amount_to_buy, None);
+
+        // --- Verify Deposit ---
+        let attached_deposit = env::attached_deposit().as_yoctonear(); // u128
+        if attached_deposit < total_price {
+            env::panic_str(&format!(
+                "Insufficient deposit. Attached: {}, Required: {}", attached_deposit, total_price
+            ));
+        }
+
+        // --- Collect Platform Fee, Pay Royalties & Seller ---
+        let market_fee = self.internal_collect_market_fee(&token_class_id, total_price, None);
+        let proceeds = total_price - market_fee;
+        let payout = self.internal_compute_payout(&token_class_id, &seller_id, proceeds);
+        let royalty = self.internal_royalty_shares(&token_class_id, proceeds)
+            .iter()
+            .map(|(_, share)| share)
+            .sum::<u128>();
+        self.internal_credit_all(&token_class_id, payout.into_iter().collect(), Some(&buyer_id));
+
+        // --- Charge Storage to the Buyer ---
+        // The rest of the deposit goes to the buyer's storage balance and pays for their new entries
+        self.internal_charge_storage(&buyer_id, initial_storage_usage, attached_deposit - total_price);
+
+        MusicSftEvent::Sale(vec![SaleLog {
+            seller_id,
+            buyer_id,
+            token_class_id,
+            amount: U128(u128::from(amount_to_buy)),
+            price: U128(total_price),
+            royalty: Some(U128(royalty)),
+            market_fee: Some(U128(market_fee)),
+            ft_token_id: None,
+        }]).emit();
+    }
+
+    // --- Listing View functions ---
+
+    pub fn market_get_listing(&self, seller_id: AccountId, token_class_id: String) -> Option<Listing> {
+        self.listings.get(&(seller_id, token_class_id)).cloned()
+    }
+
+    pub fn market_listings_count_for_class(&self, token_class_id: String) -> u32 {
+        self.listings_by_class.get(&token_class_id).map(|sellers| sellers.len()).unwrap_or(0)
+    }
+
+    /// Returns a page of listings for a token class, expired ones included.
+    pub fn market_listings_for_class(&self, token_class_id: String, from_index: Option<u64>, limit: Option<u64>) -> Vec<Listing> {
+        let Some(sellers) = self.listings_by_class.get(&token_class_id) else {
+            return Vec::new();
+        };
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(sellers.len() as u64);
+
+        sellers
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .filter_map(|seller_id| self.listings.get(&(seller_id.clone(), token_class_id.clone())).cloned())
+            .collect()
+    }
+
+    /// Returns a page of a seller's listings, expired ones included.
+    pub fn market_listings_by_seller(&self, seller_id: AccountId, from_index: Option<u64>, limit: Option<u64>) -> Vec<Listing> {
+        let Some(class_ids) = self.listings_by_seller.get(&seller_id) else {
+            return Vec::new();
+        };
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(class_ids.len() as u64);
+
+        class_ids
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .filter_map(|token_class_id| self.listings.get(&(seller_id.clone(), token_class_id.clone())).cloned())
+            .collect()
+    }
+}
@@ -0,0 +1,72 @@
+// --- Delegated Minters ---
+// Besides the creator recorded in `creators`, a class can have accounts the creator
+// trusts to mint more copies of it (a label, a co-producer, a drop bot).
+
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: whether an account may mint more copies of an existing class
+    pub(crate) fn internal_is_minter(&self, token_class_id: &str, account_id: &AccountId) -> bool {
+        self.creators.get(token_class_id).is_some_and(|creator_id| creator_id == account_id)
+            || self.class_minters.get(token_class_id)
+                .is_some_and(|minters| minters.contains(account_id))
+    }
+
+    /// Allows an account to mint additional copies of a class. Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn sft_add_minter(&mut self, token_class_id: String, account_id: AccountId) {
+        let creator_id = self.assert_class_creator(&token_class_id);
+        assert_ne!(creator_id, account_id, "The creator can always mint");
+        let initial_storage_usage = env::storage_usage();
+
+        let mut minters = self.class_minters.remove(&token_class_id).unwrap_or_default();
+        assert!(!minters.contains(&account_id), "Account is already a minter for this token class");
+        minters.push(account_id.clone());
+        self.class_minters.insert(token_class_id.clone(), minters);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MusicSftEvent::MinterAdd(vec![MinterLog {
+            token_class_id,
+            minter_id: account_id,
+            creator_id,
+        }]).emit();
+    }
+
+    /// Revokes an account's right to mint copies of a class. Only the class creator can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_remove_minter(&mut self, token_class_id: String, account_id: AccountId) {
+        assert_one_yocto();
+        let creator_id = self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let mut minters = self.class_minters.remove(&token_class_id).unwrap_or_default();
+        let position = minters.iter().position(|minter_id| *minter_id == account_id)
+            .expect("Account is not a minter for this token class");
+        minters.remove(position);
+        if !minters.is_empty() {
+            self.class_minters.insert(token_class_id.clone(), minters);
+        }
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        MusicSftEvent::MinterRemove(vec![MinterLog {
+            token_class_id,
+            minter_id: account_id,
+            creator_id,
+        }]).emit();
+    }
+
+    /// Returns every account that can currently mint copies of a class, the creator first.
+    pub fn sft_minters(&self, token_class_id: String) -> Vec<AccountId> {
+        self.creators.get(&token_class_id)
+            .into_iter()
+            .chain(self.class_minters.get(&token_class_id).into_iter().flatten())
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,440 @@
+// --- NEP-245 Multi Token Core ---
+// Each SFT token class is exposed as a NEP-245 token ID, backed by the same
+// `balances` / `token_supply` maps used by the `sft_*` methods.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{
+    env, ext_contract, near_bindgen, require, AccountId, Gas, PromiseOrValue, PromiseResult,
+};
+
+use crate::mt_metadata::MtTokenMetadata;
+use crate::*;
+
+pub const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(10);
+pub const GAS_FOR_MT_TRANSFER_CALL: Gas = Gas::from_tgas(25); // Kept for the rest of the calling method
+pub const MIN_GAS_FOR_RECEIVER: Gas = Gas::from_tgas(5); // Least gas a transfer call hands the receiver's hook
+
+/// Approval passed to NEP-245 transfer methods: (owner_id, approval_id).
+/// Allowances here are amount-based, so the approval ID is not checked.
+pub type MtApproval = (AccountId, u64);
+
+/// Approvals consumed by a transfer, handed to `mt_resolve_transfer`:
+/// (approved_account_id, approval_id, amount).
+pub type MtConsumedApproval = (AccountId, u64, U128);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtToken {
+    pub token_id: String,
+    // Copies are fungible within a class, so there is no single owner
+    pub owner_id: Option<AccountId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MtTokenMetadata>,
+}
+
+#[ext_contract(ext_mt_receiver)]
+pub trait MultiTokenReceiver {
+    fn mt_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_ids: Vec<AccountId>,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>>;
+}
+
+// Converts a NEP-245 U128 amount into the u64 used for balances
+pub(crate) fn mt_amount(amount: U128) -> u64 {
+    u64::try_from(amount.0).unwrap_or_else(|_| env::panic_str("Amount exceeds maximum balance"))
+}
+
+pub(crate) fn assert_one_yocto() {
+    assert_eq!(
+        env::attached_deposit().as_yoctonear(),
+        1,
+        "Requires attached deposit of exactly 1 yoctoNEAR"
+    );
+}
+
+// For calls that also settle storage: the yoctoNEAR proves a full access key, anything more tops up the storage balance
+pub(crate) fn assert_at_least_one_yocto() {
+    assert!(
+        env::attached_deposit().as_yoctonear() >= 1,
+        "Requires attached deposit of at least 1 yoctoNEAR"
+    );
+}
+
+// Panics unless the call has gas for itself, the resolve callback and a receiver hook that can do some work,
+// so no copies move toward a receiver that is bound to run out of gas
+pub(crate) fn assert_transfer_call_gas(gas_for_call: Gas) {
+    let required = gas_for_call
+        .saturating_add(GAS_FOR_RESOLVE_TRANSFER)
+        .saturating_add(MIN_GAS_FOR_RECEIVER);
+    require!(
+        env::prepaid_gas() >= required,
+        format!("Attach at least {} Tgas for a transfer call", required.as_tgas())
+    );
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: moves copies for a NEP-245 transfer, consuming an approval if given.
+    // Returns the approval that was used, if any, and the transfer log for the caller to emit.
+    fn internal_mt_transfer(
+        &mut self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &str,
+        amount: u64,
+        approval: Option<MtApproval>,
+        memo: Option<String>,
+    ) -> (Option<MtConsumedApproval>, MtTransferLog) {
+        assert!(amount > 0, "Transfer amount must be positive");
+
+        let (owner_id, consumed_approval) = match approval {
+            Some((owner_id, approval_id)) => {
+                assert_ne!(owner_id, *sender_id, "Owner cannot transfer from themselves using an approval");
+                self.internal_use_approval(&owner_id, sender_id, token_id, amount);
+                (owner_id, Some((sender_id.clone(), approval_id, U128(u128::from(amount)))))
+            }
+            None => (sender_id.clone(), None),
+        };
+        assert_ne!(owner_id, *receiver_id, "Sender and receiver cannot be the same");
+
+        self.internal_transfer(&owner_id, receiver_id, token_id, amount, sender_id);
+
+        let transfer_log = MtTransferLog {
+            authorized_id: consumed_approval.as_ref().map(|(account_id, _, _)| account_id.clone()),
+            old_owner_id: owner_id,
+            new_owner_id: receiver_id.clone(),
+            token_ids: vec![token_id.to_string()],
+            amounts: vec![U128(u128::from(amount))],
+            memo,
+        };
+
+        (consumed_approval, transfer_log)
+    }
+
+    // Internal helper for transfer_call resolution: moves up to `unused` copies back from the
+    // receiver and gives back the allowance of every approved account that spent them. `storage_payer`
+    // is the account the callback charges. Returns the refunded amount with the logs describing it.
+    pub(crate) fn internal_refund_unused(
+        &mut self,
+        previous_owner_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &str,
+        unused: u64,
+        approved_account_ids: &[AccountId],
+        storage_payer: &AccountId,
+    ) -> (u64, Option<MtTransferLog>, Vec<ApproveLog>) {
+        // The receiver may have already moved some copies on
+        let refund = unused.min(self.internal_balance_of(receiver_id, token_id));
+        if refund == 0 {
+            return (0, None, Vec::new());
+        }
+
+        self.internal_transfer(receiver_id, previous_owner_id, token_id, refund, storage_payer);
+
+        let approve_logs = approved_account_ids
+            .iter()
+            .map(|approved_account_id| {
+                let approved_amount = self.internal_restore_approval(
+                    previous_owner_id,
+                    approved_account_id,
+                    token_id,
+                    refund,
+                );
+                ApproveLog {
+                    owner_id: previous_owner_id.clone(),
+                    approved_account_id: approved_account_id.clone(),
+                    token_class_id: token_id.to_string(),
+                    amount: U128(u128::from(approved_amount)),
+                }
+            })
+            .collect();
+
+        let refund_log = MtTransferLog {
+            authorized_id: None,
+            old_owner_id: receiver_id.clone(),
+            new_owner_id: previous_owner_id.clone(),
+            token_ids: vec![token_id.to_string()],
+            amounts: vec![U128(u128::from(refund))],
+            memo: Some("refund".to_string()),
+        };
+
+        (refund, Some(refund_log), approve_logs)
+    }
+
+    // Internal helper shared by the single and batch transfer_call entry points
+    fn internal_mt_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<MtApproval>>>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>> {
+        require!(!token_ids.is_empty(), "Token IDs cannot be empty");
+        require!(token_ids.len() == amounts.len(), "Token IDs and amounts must have the same length");
+        if let Some(approvals) = approvals.as_ref() {
+            require!(approvals.len() == token_ids.len(), "Approvals must match the number of token IDs");
+        }
+        assert_transfer_call_gas(GAS_FOR_MT_TRANSFER_CALL);
+        let sender_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        let mut previous_owner_ids = Vec::with_capacity(token_ids.len());
+        let mut consumed_approvals = Vec::with_capacity(token_ids.len());
+        let mut transfer_logs = Vec::with_capacity(token_ids.len());
+        for (index, token_id) in token_ids.iter().enumerate() {
+            let approval = approvals.as_ref().and_then(|approvals| approvals[index].clone());
+            let (consumed, transfer_log) = self.internal_mt_transfer(
+                &sender_id,
+                &receiver_id,
+                token_id,
+                mt_amount(amounts[index]),
+                approval,
+                memo.clone(),
+            );
+            previous_owner_ids.push(transfer_log.old_owner_id.clone());
+            consumed_approvals.push(consumed.map(|approval| vec![approval]));
+            transfer_logs.push(transfer_log);
+        }
+        MtEvent::MtTransfer(transfer_logs).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        let has_approvals = consumed_approvals.iter().any(Option::is_some);
+
+        // The receiver gets what is left once this call and the resolve callback are provided for
+        let receiver_gas = env::prepaid_gas()
+            .saturating_sub(env::used_gas())
+            .saturating_sub(GAS_FOR_MT_TRANSFER_CALL)
+            .saturating_sub(GAS_FOR_RESOLVE_TRANSFER);
+
+        ext_mt_receiver::ext(receiver_id.clone())
+            .with_static_gas(receiver_gas)
+            .mt_on_transfer(
+                sender_id,
+                previous_owner_ids.clone(),
+                token_ids.clone(),
+                amounts.clone(),
+                msg,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_TRANSFER)
+                    .mt_resolve_transfer(
+                        previous_owner_ids,
+                        receiver_id,
+                        token_ids,
+                        amounts,
+                        has_approvals.then_some(consumed_approvals),
+                    ),
+            )
+            .into()
+    }
+
+    /// Transfers copies of a token class (NEP-245). The predecessor sends its own copies,
+    /// or the owner's copies when `approval` names an owner who approved the predecessor.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        amount: U128,
+        approval: Option<MtApproval>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let (_, transfer_log) =
+            self.internal_mt_transfer(&sender_id, &receiver_id, &token_id, mt_amount(amount), approval, memo);
+        MtEvent::MtTransfer(vec![transfer_log]).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Transfers copies of several token classes to one receiver (NEP-245).
+    #[payable]
+    pub fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<MtApproval>>>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        require!(!token_ids.is_empty(), "Token IDs cannot be empty");
+        require!(token_ids.len() == amounts.len(), "Token IDs and amounts must have the same length");
+        if let Some(approvals) = approvals.as_ref() {
+            require!(approvals.len() == token_ids.len(), "Approvals must match the number of token IDs");
+        }
+        let sender_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+
+        let transfer_logs = token_ids
+            .iter()
+            .enumerate()
+            .map(|(index, token_id)| {
+                let approval = approvals.as_ref().and_then(|approvals| approvals[index].clone());
+                let (_, transfer_log) = self.internal_mt_transfer(
+                    &sender_id,
+                    &receiver_id,
+                    token_id,
+                    mt_amount(amounts[index]),
+                    approval,
+                    memo.clone(),
+                );
+                transfer_log
+            })
+            .collect();
+        MtEvent::MtTransfer(transfer_logs).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Transfers copies to a contract and calls `mt_on_transfer` on it (NEP-245).
+    /// Copies the receiver reports as unused are returned in `mt_resolve_transfer`.
+    #[payable]
+    pub fn mt_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        amount: U128,
+        approval: Option<MtApproval>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>> {
+        assert_one_yocto();
+        self.internal_mt_transfer_call(receiver_id, vec![token_id], vec![amount], approval.map(|approval| vec![Some(approval)]), memo, msg)
+    }
+
+    /// Batch variant of `mt_transfer_call` (NEP-245).
+    #[payable]
+    pub fn mt_batch_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<MtApproval>>>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<Vec<U128>> {
+        assert_one_yocto();
+        self.internal_mt_transfer_call(receiver_id, token_ids, amounts, approvals, memo, msg)
+    }
+
+    /// Callback after `mt_on_transfer`: returns unused copies to their previous owners
+    /// and restores any allowance that was consumed for them. The account that sent the copies pays for the storage.
+    /// Returns the amounts that were actually transferred.
+    #[private]
+    pub fn mt_resolve_transfer(
+        &mut self,
+        previous_owner_ids: Vec<AccountId>,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        approvals: Option<Vec<Option<Vec<MtConsumedApproval>>>>,
+    ) -> Vec<U128> {
+        let initial_storage_usage = env::storage_usage();
+        // If the receiver failed or returned garbage, every copy is treated as unused
+        let unused_amounts: Vec<U128> = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                match near_sdk::serde_json::from_slice::<Vec<U128>>(&value) {
+                    Ok(unused) if unused.len() == amounts.len() => unused,
+                    _ => amounts.clone(),
+                }
+            }
+            PromiseResult::Failed => amounts.clone(),
+        };
+        // The sender used an approval for some class, or else moved their own copies
+        let sender_id = approvals
+            .iter()
+            .flatten()
+            .flatten()
+            .flatten()
+            .map(|(account_id, _, _)| account_id)
+            .next()
+            .unwrap_or(&previous_owner_ids[0])
+            .clone();
+
+        let mut refund_logs = Vec::new();
+        let mut approve_logs = Vec::new();
+        let transferred_amounts = token_ids
+            .iter()
+            .enumerate()
+            .map(|(index, token_id)| {
+                let amount = mt_amount(amounts[index]);
+                let unused = mt_amount(unused_amounts[index]).min(amount);
+                let approved_account_ids: Vec<AccountId> = approvals
+                    .as_ref()
+                    .and_then(|approvals| approvals[index].as_ref())
+                    .map(|consumed| consumed.iter().map(|(account_id, _, _)| account_id.clone()).collect())
+                    .unwrap_or_default();
+
+                let (refund, refund_log, mut restored) = self.internal_refund_unused(
+                    &previous_owner_ids[index],
+                    &receiver_id,
+                    token_id,
+                    unused,
+                    &approved_account_ids,
+                    &sender_id,
+                );
+                refund_logs.extend(refund_log);
+                approve_logs.append(&mut restored);
+
+                U128(u128::from(amount - refund))
+            })
+            .collect();
+
+        if !refund_logs.is_empty() {
+            MtEvent::MtTransfer(refund_logs).emit();
+        }
+        if !approve_logs.is_empty() {
+            MusicSftEvent::Approve(approve_logs).emit();
+        }
+
+        self.internal_charge_storage_owed(&sender_id, initial_storage_usage, 0);
+        transferred_amounts
+    }
+
+    // --- NEP-245 View functions ---
+
+    pub fn mt_balance_of(&self, account_id: AccountId, token_id: String) -> U128 {
+        U128(u128::from(self.internal_balance_of(&account_id, &token_id)))
+    }
+
+    pub fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<String>) -> Vec<U128> {
+        token_ids
+            .iter()
+            .map(|token_id| U128(u128::from(self.internal_balance_of(&account_id, token_id))))
+            .collect()
+    }
+
+    pub fn mt_supply(&self, token_id: String) -> Option<U128> {
+        self.token_supply.get(&token_id).map(|supply| U128(u128::from(*supply)))
+    }
+
+    pub fn mt_batch_supply(&self, token_ids: Vec<String>) -> Vec<Option<U128>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| self.mt_supply(token_id))
+            .collect()
+    }
+
+    pub fn mt_token(&self, token_ids: Vec<String>) -> Vec<Option<MtToken>> {
+        token_ids
+            .into_iter()
+            .map(|token_id| {
+                self.token_metadata.get(&token_id).map(|metadata| MtToken {
+                    token_id: token_id.clone(),
+                    owner_id: None,
+                    metadata: Some(MtTokenMetadata::from(metadata)),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,142 @@
+// --- NEP-245 Multi Token Metadata ---
+// Every token class is its own base metadata entry, so base IDs equal token IDs.
+
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::near_bindgen;
+
+use crate::*;
+
+pub const MT_METADATA_SPEC: &str = "mt-1.0.0";
+pub const MT_CONTRACT_NAME: &str = "Synphonic Music Editions";
+pub const MT_CONTRACT_SYMBOL: &str = "SYNPH";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtBaseTokenMetadata {
+    pub name: String,
+    pub id: String,
+    pub symbol: Option<String>,
+    pub icon: Option<String>,
+    pub decimals: Option<String>,
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub copies: Option<u64>,
+    pub reference_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtTokenMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<String>, // Passed through as stored in NFTMetadata
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub extra: Option<String>, // JSON with the cover photo and price per copy
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MtTokenMetadataAll {
+    pub base: MtBaseTokenMetadata,
+    pub token: MtTokenMetadata,
+}
+
+impl From<&NFTMetadata> for MtTokenMetadata {
+    fn from(metadata: &NFTMetadata) -> Self {
+        let extra = near_sdk::serde_json::json!({
+            "cover_photo": metadata.cover_photo,
+            "price_per_copy": metadata.price_per_copy,
+        });
+        Self {
+            title: Some(metadata.title.clone()),
+            description: Some(metadata.description.clone()),
+            media: Some(metadata.media.clone()),
+            media_hash: Some(metadata.media_hash.clone()),
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some(extra.to_string()),
+            reference: None,
+            reference_hash: None,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: builds base metadata for a token class
+    fn internal_mt_base_metadata(&self, token_id: &str) -> Option<MtBaseTokenMetadata> {
+        self.token_metadata.get(token_id).map(|metadata| MtBaseTokenMetadata {
+            name: metadata.title.clone(),
+            id: token_id.to_string(),
+            symbol: None,
+            icon: Some(metadata.cover_photo.clone()),
+            decimals: None,
+            base_uri: None,
+            reference: None,
+            copies: self.token_supply.get(token_id).copied(),
+            reference_hash: None,
+        })
+    }
+
+    pub fn mt_metadata_contract(&self) -> MtContractMetadata {
+        MtContractMetadata {
+            spec: MT_METADATA_SPEC.to_string(),
+            name: MT_CONTRACT_NAME.to_string(),
+            symbol: MT_CONTRACT_SYMBOL.to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    pub fn mt_metadata_token_all(&self, token_ids: Vec<String>) -> Vec<Option<MtTokenMetadataAll>> {
+        token_ids
+            .iter()
+            .map(|token_id| {
+                let token = self.token_metadata.get(token_id).map(MtTokenMetadata::from)?;
+                let base = self.internal_mt_base_metadata(token_id)?;
+                Some(MtTokenMetadataAll { base, token })
+            })
+            .collect()
+    }
+
+    pub fn mt_metadata_token_by_token_id(&self, token_ids: Vec<String>) -> Vec<Option<MtTokenMetadata>> {
+        token_ids
+            .iter()
+            .map(|token_id| self.token_metadata.get(token_id).map(MtTokenMetadata::from))
+            .collect()
+    }
+
+    pub fn mt_metadata_base_by_token_id(&self, token_ids: Vec<String>) -> Vec<Option<MtBaseTokenMetadata>> {
+        token_ids
+            .iter()
+            .map(|token_id| self.internal_mt_base_metadata(token_id))
+            .collect()
+    }
+
+    pub fn mt_metadata_base_by_metadata_id(&self, base_metadata_ids: Vec<String>) -> Vec<Option<MtBaseTokenMetadata>> {
+        // Base metadata IDs are the token class IDs themselves
+        self.mt_metadata_base_by_token_id(base_metadata_ids)
+    }
+}
@@ -0,0 +1,240 @@
+// --- Offers ---
+// Buyers bid on a token class by escrowing NEAR for `quantity` copies at their own price.
+// Every buyer keeps at most one offer per class; any holder of the class can fill it,
+// fully or in part, with `accept_offer`. Cancelling returns the escrow still held.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::store::IterableSet;
+use near_sdk::{env, near_bindgen, AccountId, NearToken, Promise};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    pub buyer_id: AccountId,
+    pub token_class_id: String,
+    pub price_per_copy: U128,
+    pub quantity: U64, // Copies still wanted, escrowed at `price_per_copy` each
+    pub expires_at: Option<U64>, // Block timestamp in nanoseconds
+}
+
+impl Offer {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0)
+    }
+
+    // NEAR held in escrow for the copies still wanted
+    pub fn escrow(&self) -> u128 {
+        self.price_per_copy.0 * u128::from(self.quantity.0)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: reports the current state of an offer, quantity 0 when it is gone
+    fn internal_emit_offer_update(&self, offer: &Offer) {
+        MusicSftEvent::OfferUpdate(vec![OfferUpdateLog {
+            buyer_id: offer.buyer_id.clone(),
+            token_class_id: offer.token_class_id.clone(),
+            amount: U128(u128::from(offer.quantity.0)),
+            price_per_copy: offer.price_per_copy,
+            expires_at: offer.expires_at,
+        }]).emit();
+    }
+
+    // Internal helper: removes an offer and drops it from the class index
+    fn internal_remove_offer(&mut self, buyer_id: &AccountId, token_class_id: &str) -> Offer {
+        let offer = self.offers.remove(&(buyer_id.clone(), token_class_id.to_string()))
+            .expect("Offer not found");
+
+        if let Some(buyers) = self.offers_by_class.get_mut(token_class_id) {
+            buyers.remove(buyer_id);
+            buyers.flush();
+            if buyers.is_empty() {
+                self.offers_by_class.remove(token_class_id);
+            }
+        }
+        offer
+    }
+
+    /// Offers to buy `quantity` copies of a token class at `price_per_copy`, replacing any earlier offer
+    /// (whose escrow is refunded). `expires_at` is a block timestamp in nanoseconds after which the offer
+    /// can no longer be accepted. The attached deposit must cover the full price; the rest goes to the
+    /// predecessor's storage balance, which pays for the offer.
+    #[payable]
+    pub fn market_make_offer(
+        &mut self,
+        token_class_id: String,
+        price_per_copy: U128,
+        quantity: U64,
+        expires_at: Option<U64>
+    ) {
+        let buyer_id = env::predecessor_account_id();
+        assert!(quantity.0 > 0, "Offer quantity must be positive");
+        assert!(price_per_copy.0 > 0, "Offer price must be positive");
+        assert!(self.token_classes.contains(&token_class_id), "Token class ID does not exist");
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > env::block_timestamp(), "Offer expiry must be in the future");
+        }
+        let escrow = price_per_copy.0.checked_mul(u128::from(quantity.0))
+            .expect("Price overflow");
+        let initial_storage_usage = env::storage_usage();
+
+        // --- Release Any Earlier Offer ---
+        let released = if self.offers.contains_key(&(buyer_id.clone(), token_class_id.clone())) {
+            self.internal_remove_offer(&buyer_id, &token_class_id).escrow()
+        } else {
+            0
+        };
+
+        let offer = Offer {
+            buyer_id: buyer_id.clone(),
+            token_class_id: token_class_id.clone(),
+            price_per_copy,
+            quantity,
+            expires_at,
+        };
+        self.internal_emit_offer_update(&offer);
+        let buyers = self.offers_by_class
+            .entry(token_class_id.clone())
+            .or_insert_with(|| {
+                IterableSet::new(StorageKey::OffersByClassInner {
+                    token_class_hash: env::sha256_array(token_class_id.as_bytes()),
+                })
+            });
+        buyers.insert(buyer_id.clone());
+        buyers.flush();
+        self.offers.insert((buyer_id.clone(), token_class_id), offer);
+
+        // --- Verify Deposit & Charge Storage ---
+        let available = env::attached_deposit().as_yoctonear() + released;
+        if available < escrow {
+            env::panic_str(&format!(
+                "Insufficient deposit. Available: {}, Required: {}", available, escrow
+            ));
+        }
+        // The rest of the deposit goes to the buyer's storage balance and pays for the offer entries
+        self.internal_charge_storage(&buyer_id, initial_storage_usage, available - escrow);
+    }
+
+    /// Withdraws the predecessor's offer for a token class and refunds its escrow.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn market_cancel_offer(&mut self, token_class_id: String) {
+        assert_one_yocto();
+        let buyer_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let mut offer = self.internal_remove_offer(&buyer_id, &token_class_id);
+        let escrow = offer.escrow();
+        offer.quantity = U64(0);
+        self.internal_emit_offer_update(&offer);
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+
+        if escrow > 0 {
+            Promise::new(buyer_id).transfer(NearToken::from_yoctonear(escrow));
+        }
+    }
+
+    /// Sells `quantity` copies (default: all the offer still wants) to a buyer's offer at the offered price.
+    /// The escrowed price pays the platform fee, royalties and the seller (predecessor).
+    /// The seller pays for the storage the sale adds, from their storage balance topped up by the attached
+    /// deposit; the buyer gets back the storage of a filled offer.
+    /// Requires at least 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn accept_offer(&mut self, token_class_id: String, buyer_id: AccountId, quantity: Option<U64>) {
+        assert_at_least_one_yocto();
+        let seller_id = env::predecessor_account_id();
+        assert_ne!(buyer_id, seller_id, "Buyer and seller cannot be the same");
+        self.assert_released(&token_class_id);
+        self.assert_purchase_rules(&token_class_id, &buyer_id);
+        let initial_storage_usage = env::storage_usage();
+
+        // --- Get Offer & Price ---
+        let offer_key = (buyer_id.clone(), token_class_id.clone());
+        let mut offer = self.offers.get(&offer_key)
+            .expect("Offer not found")
+            .clone();
+        assert!(!offer.is_expired(), "Offer has expired");
+        let amount_to_sell: u64 = quantity.map(|quantity| quantity.0).unwrap_or(offer.quantity.0);
+        assert!(amount_to_sell > 0, "Sale quantity must be positive");
+        if offer.quantity.0 < amount_to_sell {
+            env::panic_str(&format!(
+                "Offer does not want that many copies. Wanted: {}, Offered: {}", offer.quantity.0, amount_to_sell
+            ));
+        }
+        let total_price = offer.price_per_copy.0 * u128::from(amount_to_sell);
+
+        // --- Decrement Offer ---
+        offer.quantity = U64(offer.quantity.0 - amount_to_sell);
+        if offer.quantity.0 == 0 {
+            self.internal_remove_paid_by(Some(&buyer_id), |this| this.internal_remove_offer(&buyer_id, &token_class_id));
+        } else {
+            self.offers.insert(offer_key, offer.clone());
+        }
+        self.internal_emit_offer_update(&offer);
+
+        // --- Transfer SFT Copies to Buyer ---
+        self.internal_transfer(&seller_id, &buyer_id, &token_class_id, amount_to_sell, &seller_id);
+
+        // --- Collect Platform Fee, Pay Royalties & Seller ---
+        let market_fee = self.internal_collect_market_fee(&token_class_id, total_price, None);
+        let proceeds = total_price - market_fee;
+        let payout = self.internal_compute_payout(&token_class_id, &seller_id, proceeds);
+        let royalty = self.internal_royalty_shares(&token_class_id, proceeds)
+            .iter()
+            .map(|(_, share)| share)
+            .sum::<u128>();
+        self.internal_credit_all(&token_class_id, payout.into_iter().collect(), Some(&seller_id));
+
+        // --- Logging ---
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id: Some(env::current_account_id()), // Indicates marketplace facilitated
+            old_owner_id: seller_id.clone(),
+            new_owner_id: buyer_id.clone(),
+            token_ids: vec![token_class_id.clone()],
+            amounts: vec![U128(u128::from(amount_to_sell))],
+            memo: Some("Offer accepted".to_string()),
+        }]).emit();
+        MusicSftEvent::Sale(vec![SaleLog {
+            seller_id: seller_id.clone(),
+            buyer_id: buyer_id.clone(),
+            token_class_id,
+            amount: U128(u128::from(amount_to_sell)),
+            price: U128(total_price),
+            royalty: Some(U128(royalty)),
+            market_fee: Some(U128(market_fee)),
+            ft_token_id: None,
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // --- Offer View functions ---
+
+    pub fn market_get_offer(&self, buyer_id: AccountId, token_class_id: String) -> Option<Offer> {
+        self.offers.get(&(buyer_id, token_class_id)).cloned()
+    }
+
+    pub fn market_offers_count_for_class(&self, token_class_id: String) -> u32 {
+        self.offers_by_class.get(&token_class_id).map(|buyers| buyers.len()).unwrap_or(0)
+    }
+
+    /// Returns a page of the offer book for a token class, expired offers included.
+    pub fn market_offers_for_class(&self, token_class_id: String, from_index: Option<u64>, limit: Option<u64>) -> Vec<Offer> {
+        let Some(buyers) = self.offers_by_class.get(&token_class_id) else {
+            return Vec::new();
+        };
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(buyers.len() as u64);
+
+        buyers
+            .iter()
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .filter_map(|buyer_id| self.offers.get(&(buyer_id.clone(), token_class_id.clone())).cloned())
+            .collect()
+    }
+}
@@ -0,0 +1,161 @@
+// --- Allowlist Presale ---
+// Until `ends_at` a class's primary sale is limited to allowlisted accounts, each capped at
+// `per_account_limit` copies. Accounts are allowlisted one by one, or through a Merkle root
+// whose leaves are sha256(account_id); a proof hashes sorted pairs on the way up.
+// Once the presale ends, `buy_primary` is open to everyone.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Presale {
+    pub ends_at: U64, // Block timestamp in nanoseconds when the public sale takes over
+    pub per_account_limit: Option<U64>, // Copies each allowlisted account can buy during the presale
+    pub merkle_root: Option<Base64VecU8>, // 32 byte root over sha256(account_id) leaves
+}
+
+impl Presale {
+    pub fn is_active(&self) -> bool {
+        env::block_timestamp() < self.ends_at.0
+    }
+}
+
+// Checks a Merkle proof for `account_id` against `root`, hashing sorted pairs
+fn verify_merkle_proof(root: &[u8], account_id: &AccountId, proof: &[Base64VecU8]) -> bool {
+    let mut hash = env::sha256(account_id.as_bytes());
+    for sibling in proof {
+        let (left, right) = if hash <= sibling.0 { (&hash, &sibling.0) } else { (&sibling.0, &hash) };
+        hash = env::sha256(&[left.as_slice(), right.as_slice()].concat());
+    }
+    hash == root
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: whether an account is on a class's presale allowlist
+    fn internal_is_allowlisted(&self, token_class_id: &str, account_id: &AccountId, proof: Option<&[Base64VecU8]>) -> bool {
+        if self.presale_allowlist.contains(&(token_class_id.to_string(), account_id.clone())) {
+            return true;
+        }
+        let Some(presale) = self.presales.get(token_class_id) else {
+            return false;
+        };
+        match (presale.merkle_root.as_ref(), proof) {
+            (Some(root), Some(proof)) => verify_merkle_proof(&root.0, account_id, proof),
+            _ => false,
+        }
+    }
+
+    // Internal helper: while the presale runs, panics unless the buyer is allowlisted and within
+    // their limit, then counts the copies against it
+    pub(crate) fn internal_use_presale(
+        &mut self,
+        token_class_id: &str,
+        buyer_id: &AccountId,
+        amount: u64,
+        proof: Option<&[Base64VecU8]>,
+    ) {
+        let Some(presale) = self.presales.get(token_class_id).filter(|presale| presale.is_active()) else {
+            return;
+        };
+        let per_account_limit = presale.per_account_limit;
+        assert!(
+            self.internal_is_allowlisted(token_class_id, buyer_id, proof),
+            "Presale is limited to allowlisted accounts"
+        );
+
+        let key = (token_class_id.to_string(), buyer_id.clone());
+        let purchased = self.presale_purchases.get(&key).copied().unwrap_or(0) + amount;
+        if let Some(limit) = per_account_limit {
+            if purchased > limit.0 {
+                env::panic_str(&format!(
+                    "Presale limit exceeded. Limit: {}, Requested total: {}", limit.0, purchased
+                ));
+            }
+        }
+        self.presale_purchases.insert(key, purchased);
+    }
+
+    // Internal helper: stores or clears a class's presale
+    pub(crate) fn internal_set_presale(&mut self, token_class_id: &str, presale: Option<Presale>) {
+        let Some(presale) = presale else {
+            self.presales.remove(token_class_id);
+            return;
+        };
+        assert!(presale.is_active(), "Presale end must be in the future");
+        if let Some(root) = presale.merkle_root.as_ref() {
+            assert_eq!(root.0.len(), 32, "Merkle root must be 32 bytes");
+        }
+        if let Some(limit) = presale.per_account_limit {
+            assert!(limit.0 > 0, "Presale limit must be positive");
+        }
+        self.presales.insert(token_class_id.to_string(), presale);
+    }
+
+    /// Sets or clears the presale of a class. Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    /// Requires at least 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_set_presale(&mut self, token_class_id: String, presale: Option<Presale>) {
+        assert_at_least_one_yocto();
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+        self.internal_set_presale(&token_class_id, presale.clone());
+
+        MusicSftEvent::PresaleUpdate(vec![PresaleLog { token_class_id, presale }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Adds accounts to a class's presale allowlist. Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn sft_add_to_allowlist(&mut self, token_class_id: String, account_ids: Vec<AccountId>) {
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+
+        for account_id in account_ids.iter() {
+            self.presale_allowlist.insert((token_class_id.clone(), account_id.clone()));
+        }
+        MusicSftEvent::AllowlistAdd(vec![AllowlistLog { token_class_id, account_ids }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Removes accounts from a class's presale allowlist. Only the class creator can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_remove_from_allowlist(&mut self, token_class_id: String, account_ids: Vec<AccountId>) {
+        assert_one_yocto();
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+
+        for account_id in account_ids.iter() {
+            self.presale_allowlist.remove(&(token_class_id.clone(), account_id.clone()));
+        }
+        MusicSftEvent::AllowlistRemove(vec![AllowlistLog { token_class_id, account_ids }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Get the presale of a token class
+    pub fn sft_presale(&self, token_class_id: String) -> Option<Presale> {
+        self.presales.get(&token_class_id).cloned()
+    }
+
+    /// Returns whether an account may buy during the class's presale, checking `proof` against
+    /// the Merkle root when the account is not listed explicitly.
+    pub fn sft_is_allowlisted(&self, token_class_id: String, account_id: AccountId, proof: Option<Vec<Base64VecU8>>) -> bool {
+        self.internal_is_allowlisted(&token_class_id, &account_id, proof.as_deref())
+    }
+
+    // Get the copies an account has bought during a class's presale
+    pub fn sft_presale_purchases(&self, token_class_id: String, account_id: AccountId) -> U64 {
+        U64(self.presale_purchases.get(&(token_class_id, account_id)).copied().unwrap_or(0))
+    }
+}
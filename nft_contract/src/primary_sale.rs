@@ -0,0 +1,154 @@
+// --- Primary Sales ---
+// The creator opens a class for sale and buyers mint copies straight from it,
+// paying `price_per_copy` to the class's split holders until the max supply is reached.
+
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: panics unless the predecessor created the class
+    pub(crate) fn assert_class_creator(&self, token_class_id: &str) -> AccountId {
+        let caller_id = env::predecessor_account_id();
+        let creator_id = self.creators.get(token_class_id)
+            .expect("Token class ID does not exist");
+        assert_eq!(caller_id, *creator_id, "Only the class creator can call this method");
+        caller_id
+    }
+
+    /// Opens a token class for primary sales at the given price, replacing any Dutch auction schedule.
+    /// Classes created without a max supply need one here; an existing cap is fixed and cannot change.
+    /// Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    /// Requires at least 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_enable_primary_sale(&mut self, token_class_id: String, max_supply: Option<U64>, price_per_copy: U128) {
+        assert_at_least_one_yocto();
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let max_supply = match (self.max_supply.get(&token_class_id).copied(), max_supply) {
+            (Some(existing), None) => U64(existing),
+            (Some(existing), Some(requested)) => {
+                assert_eq!(existing, requested.0, "Max supply is fixed once set");
+                requested
+            }
+            (None, Some(requested)) => {
+                assert!(
+                    requested.0 >= self.internal_minted(&token_class_id),
+                    "Max supply cannot be below the copies already minted"
+                );
+                self.max_supply.insert(token_class_id.clone(), requested.0);
+                requested
+            }
+            (None, None) => env::panic_str("Max supply is required to open a primary sale"),
+        };
+
+        let metadata = self.token_metadata.get_mut(&token_class_id)
+            .expect("Token class metadata not found");
+        metadata.price_per_copy = price_per_copy;
+        self.primary_sale_classes.insert(token_class_id.clone());
+        self.dutch_auctions.remove(&token_class_id);
+
+        MusicSftEvent::PrimarySaleUpdate(vec![PrimarySaleUpdateLog {
+            token_class_id,
+            enabled: true,
+            max_supply,
+            price_per_copy,
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Closes primary sales for a token class. Only the class creator can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_disable_primary_sale(&mut self, token_class_id: String) {
+        assert_one_yocto();
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+        assert!(
+            self.primary_sale_classes.remove(&token_class_id),
+            "Primary sale is not enabled for this token class"
+        );
+
+        let price_per_copy = U128(self.internal_primary_price(&token_class_id));
+        let max_supply = U64(self.max_supply.get(&token_class_id).copied().unwrap_or(0));
+        MusicSftEvent::PrimarySaleUpdate(vec![PrimarySaleUpdateLog {
+            token_class_id,
+            enabled: false,
+            max_supply,
+            price_per_copy,
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Mints copies of a class on primary sale to the predecessor.
+    /// The attached deposit must cover the current primary price (see `sft_primary_price`) for each copy
+    /// plus storage; the price is credited to the split holders' pending withdrawals.
+    /// During a presale only allowlisted accounts can buy; `proof` is their Merkle proof if not listed explicitly.
+    #[payable]
+    pub fn buy_primary(&mut self, token_class_id: String, amount: Option<U64>, proof: Option<Vec<Base64VecU8>>) {
+        let buyer_id = env::predecessor_account_id();
+        let amount_to_buy: u64 = amount.map(|amount| amount.0).unwrap_or(1);
+        assert!(amount_to_buy > 0, "Purchase amount must be positive");
+        let initial_storage_usage = env::storage_usage();
+        assert!(
+            self.primary_sale_classes.contains(&token_class_id),
+            "Primary sale is not enabled for this token class"
+        );
+        self.assert_primary_sale_open(&token_class_id);
+        self.internal_use_presale(&token_class_id, &buyer_id, amount_to_buy, proof.as_deref());
+
+        // --- Enforce Supply Cap ---
+        let max_supply = self.max_supply.get(&token_class_id).copied()
+            .expect("Token class has no max supply");
+        let remaining = max_supply.saturating_sub(self.internal_minted(&token_class_id));
+        if amount_to_buy > remaining {
+            env::panic_str(&format!(
+                "Not enough copies left. Remaining: {}, Requested: {}", remaining, amount_to_buy
+            ));
+        }
+
+        let price_per_copy = self.internal_primary_price(&token_class_id);
+        let price = price_per_copy.checked_mul(u128::from(amount_to_buy))
+            .expect("Price overflow");
+        let creator_id = self.creators.get(&token_class_id)
+            .expect("Token class creator not found")
+            .clone();
+
+        let attached_deposit = env::attached_deposit().as_yoctonear();
+        if attached_deposit < price {
+            env::panic_str(&format!(
+                "Insufficient deposit. Attached: {}, Required: {}", attached_deposit, price
+            ));
+        }
+
+        // --- Mint & Credit Split Holders ---
+        self.internal_mint(&token_class_id, &buyer_id, amount_to_buy, &buyer_id);
+        let shares = self.internal_split_amount(&token_class_id, price);
+        self.internal_credit_all(&token_class_id, shares, Some(&buyer_id));
+
+        // The rest of the deposit goes to the buyer's storage balance and pays for the new entries
+        self.internal_charge_storage(&buyer_id, initial_storage_usage, attached_deposit - price);
+
+        MusicSftEvent::PrimarySale(vec![SaleLog {
+            seller_id: creator_id,
+            buyer_id,
+            token_class_id,
+            amount: U128(u128::from(amount_to_buy)),
+            price: U128(price),
+            royalty: None,
+            market_fee: None,
+            ft_token_id: None,
+        }]).emit();
+    }
+
+    /// Returns whether a class is on primary sale.
+    pub fn sft_is_primary_sale_enabled(&self, token_class_id: String) -> bool {
+        self.primary_sale_classes.contains(&token_class_id)
+    }
+}
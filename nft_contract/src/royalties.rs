@@ -0,0 +1,171 @@
+// --- Creator Royalties & Payouts ---
+// A class can carry a royalty in basis points, fixed when the class is created in `sft_mint`.
+// Every secondary sale pays that share of the price to the class's split holders, both on our own
+// marketplace and on third-party marketplaces through the NEP-199 style payout methods.
+
+// The NEP-199 argument list plus the SFT amount and owner exceeds clippy's limit,
+// including in the cross-contract helpers `near_bindgen` generates
+#![allow(clippy::too_many_arguments)]
+
+use std::collections::HashMap;
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+pub const BASIS_POINTS: u16 = 10_000;
+pub const MAX_ROYALTY_BPS: u16 = 5_000;
+
+// Recipients in a payout until the owner sets another cap
+pub const DEFAULT_MAX_PAYOUT_RECIPIENTS: u32 = 10;
+// A payout needs room for at least one split holder and the seller
+pub const MIN_PAYOUT_RECIPIENTS: u32 = 2;
+
+/// NEP-199 payout: how a sale's balance is split between accounts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: the royalty owed on a secondary sale, shared out by the class's split sheet
+    pub(crate) fn internal_royalty_shares(&self, token_class_id: &str, price: u128) -> Vec<(AccountId, u128)> {
+        let royalty_bps = self.royalties.get(token_class_id).copied().unwrap_or(0);
+        if royalty_bps == 0 {
+            return Vec::new();
+        }
+        let royalty = price * u128::from(royalty_bps) / u128::from(BASIS_POINTS);
+        self.internal_split_amount(token_class_id, royalty)
+    }
+
+    // Internal helper: splits a sale balance into royalty shares and the seller's remainder.
+    // Our own sales pay the stored split sheet whatever its length.
+    pub(crate) fn internal_compute_payout(
+        &self,
+        token_class_id: &str,
+        owner_id: &AccountId,
+        balance: u128,
+    ) -> HashMap<AccountId, u128> {
+        let mut payout = HashMap::new();
+
+        // A seller who is also a split holder simply keeps their own share
+        let mut royalty_total = 0;
+        for (account_id, share) in self.internal_royalty_shares(token_class_id, balance) {
+            if share > 0 {
+                *payout.entry(account_id).or_insert(0) += share;
+                royalty_total += share;
+            }
+        }
+        *payout.entry(owner_id.clone()).or_insert(0) += balance - royalty_total;
+        payout
+    }
+
+    // Internal helper: the NEP-199 payout for a third-party marketplace.
+    // Panics if it would pay more than `max_len_payout` accounts or the owner's cap.
+    fn internal_nep199_payout(
+        &self,
+        token_class_id: &str,
+        owner_id: &AccountId,
+        balance: u128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        let max_len_payout = max_len_payout.unwrap_or(self.max_payout_recipients).min(self.max_payout_recipients);
+        let payout = self.internal_compute_payout(token_class_id, owner_id, balance);
+        assert!(
+            payout.len() as u32 <= max_len_payout,
+            "Payout has {} recipients, max_len_payout is {}", payout.len(), max_len_payout
+        );
+        Payout {
+            payout: payout.into_iter().map(|(account_id, share)| (account_id, U128(share))).collect(),
+        }
+    }
+
+    /// Returns how a sale of `amount` copies for `balance` yoctoNEAR (or tokens) should be split (NEP-199).
+    pub fn sft_payout(
+        &self,
+        owner_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        assert!(amount.0 > 0, "Amount must be positive");
+        assert!(
+            self.internal_balance_of(&owner_id, &token_class_id) >= amount.0,
+            "Owner does not hold enough copies"
+        );
+        self.internal_nep199_payout(&token_class_id, &owner_id, balance.0, max_len_payout)
+    }
+
+    /// Transfers copies sold on a third-party marketplace and returns the payout that
+    /// marketplace must honour (NEP-199). The predecessor sends its own copies, or `owner_id`'s
+    /// copies using its approval for the class.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_class_id: String,
+        amount: U64,
+        owner_id: AccountId,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: Option<u32>,
+    ) -> Payout {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert!(amount.0 > 0, "Transfer amount must be positive");
+        assert_ne!(owner_id, receiver_id, "Owner and receiver cannot be the same");
+        let initial_storage_usage = env::storage_usage();
+
+        let authorized_id = if sender_id != owner_id {
+            self.internal_use_approval(&owner_id, &sender_id, &token_class_id, amount.0);
+            Some(sender_id)
+        } else {
+            None
+        };
+        let payout = self.internal_nep199_payout(&token_class_id, &owner_id, balance.0, max_len_payout);
+        self.internal_transfer(&owner_id, &receiver_id, &token_class_id, amount.0, &env::predecessor_account_id());
+
+        MtEvent::MtTransfer(vec![MtTransferLog {
+            authorized_id,
+            old_owner_id: owner_id,
+            new_owner_id: receiver_id,
+            token_ids: vec![token_class_id],
+            amounts: vec![U128(u128::from(amount.0))],
+            memo,
+        }]).emit();
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        payout
+    }
+
+    /// Sets the most accounts a NEP-199 payout may pay, split holders and seller included, and so
+    /// how long new split sheets can be. Only the contract owner can call this. Sales on this
+    /// marketplace always pay the stored split sheet; only third-party payouts of sheets longer
+    /// than a lowered cap fail until the sheet is replaced with a shorter one.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn set_max_payout_recipients(&mut self, max_payout_recipients: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            max_payout_recipients >= MIN_PAYOUT_RECIPIENTS,
+            "A payout must allow at least {} recipients", MIN_PAYOUT_RECIPIENTS
+        );
+        self.max_payout_recipients = max_payout_recipients;
+    }
+
+    // Get the royalty for a token class in basis points
+    pub fn sft_royalty_bps(&self, token_class_id: String) -> u16 {
+        self.royalties.get(&token_class_id).copied().unwrap_or(0)
+    }
+
+    // Get the most accounts a payout may pay
+    pub fn get_max_payout_recipients(&self) -> u32 {
+        self.max_payout_recipients
+    }
+}
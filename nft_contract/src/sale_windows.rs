@@ -0,0 +1,166 @@
+// --- Scheduled Drops ---
+// A class can carry a sale window so a release can be announced ahead of time. Primary sales
+// only run inside the window. Before the window opens the class cannot change hands on the
+// marketplace either; after it closes copies keep trading there as usual. Upcoming drops are kept
+// in a tree ordered by start time, so listing them reads only the page asked for. Entries whose
+// sale has started are skipped by the view and pruned a few at a time whenever a window is set.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+// Most started drops one call removes from the upcoming drop index
+pub const MAX_PRUNED_DROPS_PER_CALL: usize = 3;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleWindow {
+    pub starts_at: Option<U64>, // Block timestamp in nanoseconds, open right away when unset
+    pub ends_at: Option<U64>, // Block timestamp in nanoseconds, open indefinitely when unset
+}
+
+impl SaleWindow {
+    pub fn assert_valid(&self) {
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at) {
+            assert!(starts_at.0 < ends_at.0, "Sale window must start before it ends");
+        }
+        if let Some(ends_at) = self.ends_at {
+            assert!(ends_at.0 > env::block_timestamp(), "Sale window end must be in the future");
+        }
+    }
+
+    pub fn has_started(&self) -> bool {
+        self.starts_at.is_none_or(|starts_at| env::block_timestamp() >= starts_at.0)
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.ends_at.is_some_and(|ends_at| env::block_timestamp() >= ends_at.0)
+    }
+}
+
+// A class whose sale window has not opened yet
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UpcomingDrop {
+    pub token_class_id: String,
+    pub creator_id: AccountId,
+    pub metadata: NFTMetadata,
+    pub starts_at: U64,
+    pub ends_at: Option<U64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: drops up to `MAX_PRUNED_DROPS_PER_CALL` entries whose sale has started from the
+    // front of the upcoming drop index. Each class's creator set its window, so they get the entry's storage back.
+    fn internal_prune_upcoming_drops(&mut self) {
+        let started: Vec<(u64, String)> = self.upcoming_drops
+            .range(..(env::block_timestamp() + 1, String::new()))
+            .take(MAX_PRUNED_DROPS_PER_CALL)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in started {
+            let creator_id = self.creators.get(&key.1).cloned();
+            self.internal_remove_paid_by(creator_id.as_ref(), |this| {
+                this.upcoming_drops.remove(&key);
+            });
+        }
+    }
+
+    // Internal helper: stores a class's sale window and keeps the upcoming drop index in step
+    pub(crate) fn internal_set_sale_window(&mut self, token_class_id: &str, window: Option<SaleWindow>) {
+        let previous_start = self.sale_windows.get(token_class_id).and_then(|window| window.starts_at);
+        if let Some(starts_at) = previous_start {
+            self.upcoming_drops.remove(&(starts_at.0, token_class_id.to_string()));
+        }
+        self.internal_prune_upcoming_drops();
+
+        match window {
+            Some(window) => {
+                window.assert_valid();
+                if let Some(starts_at) = window.starts_at.filter(|_| !window.has_started()) {
+                    self.upcoming_drops.insert((starts_at.0, token_class_id.to_string()), ());
+                }
+                self.sale_windows.insert(token_class_id.to_string(), window);
+            }
+            None => {
+                self.sale_windows.remove(token_class_id);
+            }
+        }
+    }
+
+    // Internal helper: panics unless the class's primary sale window is open
+    pub(crate) fn assert_primary_sale_open(&self, token_class_id: &str) {
+        let Some(window) = self.sale_windows.get(token_class_id) else {
+            return;
+        };
+        assert!(window.has_started(), "Sale has not started yet");
+        assert!(!window.has_ended(), "Sale has ended");
+    }
+
+    // Internal helper: panics while the class has not been released yet
+    pub(crate) fn assert_released(&self, token_class_id: &str) {
+        if let Some(window) = self.sale_windows.get(token_class_id) {
+            assert!(window.has_started(), "Sale has not started yet");
+        }
+    }
+
+    /// Sets or clears the sale window of a class. Only the class creator can call this.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    /// Requires at least 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_set_sale_window(&mut self, token_class_id: String, window: Option<SaleWindow>) {
+        assert_at_least_one_yocto();
+        self.assert_class_creator(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+        self.internal_set_sale_window(&token_class_id, window.clone());
+
+        // A Dutch auction whose price has not started dropping yet follows the new start
+        let now = env::block_timestamp();
+        if let Some(auction) = self.dutch_auctions.get_mut(&token_class_id) {
+            if auction.starts_at.0 > now {
+                let opens_at = window.as_ref().and_then(|window| window.starts_at).map_or(0, |starts_at| starts_at.0);
+                auction.starts_at = U64(now.max(opens_at));
+            }
+        }
+
+        MusicSftEvent::SaleWindowUpdate(vec![SaleWindowLog {
+            token_class_id,
+            starts_at: window.as_ref().and_then(|window| window.starts_at),
+            ends_at: window.and_then(|window| window.ends_at),
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Get the sale window of a token class
+    pub fn sft_sale_window(&self, token_class_id: String) -> Option<SaleWindow> {
+        self.sale_windows.get(&token_class_id).cloned()
+    }
+
+    /// Returns a page of the classes whose sale has not started yet, soonest first.
+    pub fn sft_upcoming_drops(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<UpcomingDrop> {
+        let start_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(u64::from(self.upcoming_drops.len()));
+
+        // Entries that start before the next nanosecond have started but may not be pruned yet
+        self.upcoming_drops
+            .range((env::block_timestamp() + 1, String::new())..)
+            .skip(start_index as usize)
+            .take(limit as usize)
+            .filter_map(|((_, token_class_id), _)| {
+                let window = self.sale_windows.get(token_class_id)?;
+                Some(UpcomingDrop {
+                    token_class_id: token_class_id.clone(),
+                    creator_id: self.creators.get(token_class_id)?.clone(),
+                    metadata: self.token_metadata.get(token_class_id)?.clone(),
+                    starts_at: window.starts_at?,
+                    ends_at: window.ends_at,
+                })
+            })
+            .collect()
+    }
+}
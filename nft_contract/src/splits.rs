@@ -0,0 +1,188 @@
+// --- Collaborator Split Sheets ---
+// A class's revenue (primary sales and royalties) is shared between the accounts on its
+// split sheet. Without a stored sheet the creator holds 100%. Changing the sheet takes a
+// proposal that every current split holder approves.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SplitShare {
+    pub account_id: AccountId,
+    pub share_bps: u16,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SplitProposal {
+    pub proposer_id: AccountId,
+    pub shares: Vec<SplitShare>,
+    pub approved_by: Vec<AccountId>, // Current split holders who consented so far
+}
+
+// Validates a proposed split sheet: unique accounts, positive shares summing to 100%,
+// and no more accounts than `max_recipients`
+fn assert_valid_split_sheet(shares: &[SplitShare], max_recipients: usize) {
+    assert!(!shares.is_empty(), "Split sheet cannot be empty");
+    assert!(
+        shares.len() <= max_recipients,
+        "Split sheet cannot have more than {} accounts", max_recipients
+    );
+    let mut total_bps: u32 = 0;
+    for (index, share) in shares.iter().enumerate() {
+        assert!(share.share_bps > 0, "Every split share must be positive");
+        assert!(
+            !shares[..index].iter().any(|other| other.account_id == share.account_id),
+            "Split sheet lists {} more than once", share.account_id
+        );
+        total_bps += u32::from(share.share_bps);
+    }
+    assert_eq!(total_bps, u32::from(BASIS_POINTS), "Split shares must sum to {} basis points", BASIS_POINTS);
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: the class's split sheet, defaulting to the creator holding everything
+    pub(crate) fn internal_split_sheet(&self, token_class_id: &str) -> Vec<SplitShare> {
+        if let Some(shares) = self.split_sheets.get(token_class_id) {
+            return shares.clone();
+        }
+        self.creators.get(token_class_id)
+            .map(|creator_id| vec![SplitShare { account_id: creator_id.clone(), share_bps: BASIS_POINTS }])
+            .unwrap_or_default()
+    }
+
+    // Internal helper: divides an amount by the split sheet. Rounding dust goes to the first account.
+    pub(crate) fn internal_split_amount(&self, token_class_id: &str, amount: u128) -> Vec<(AccountId, u128)> {
+        let mut parts: Vec<(AccountId, u128)> = self.internal_split_sheet(token_class_id)
+            .into_iter()
+            .map(|share| (share.account_id, amount * u128::from(share.share_bps) / u128::from(BASIS_POINTS)))
+            .collect();
+        let distributed: u128 = parts.iter().map(|(_, part)| part).sum();
+        if let Some((_, first)) = parts.first_mut() {
+            *first += amount - distributed;
+        }
+        parts
+    }
+
+    // Internal helper: panics unless the predecessor is on the class's current split sheet
+    fn assert_split_holder(&self, token_class_id: &str) -> AccountId {
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            self.internal_split_sheet(token_class_id).iter().any(|share| share.account_id == caller_id),
+            "Only current split holders can call this method"
+        );
+        caller_id
+    }
+
+    // Internal helper: applies a proposal once every current split holder has approved it
+    fn internal_try_apply_split_proposal(&mut self, token_class_id: &str) {
+        let proposal = self.split_proposals.get(token_class_id)
+            .expect("No split sheet proposal for this token class");
+        let all_approved = self.internal_split_sheet(token_class_id)
+            .iter()
+            .all(|share| proposal.approved_by.contains(&share.account_id));
+        if !all_approved {
+            return;
+        }
+
+        // The proposer paid for the proposal, so they get its storage back
+        let proposer_id = proposal.proposer_id.clone();
+        let proposal = self.internal_remove_paid_by(Some(&proposer_id), |this| this.split_proposals.remove(token_class_id).unwrap());
+        self.split_sheets.insert(token_class_id.to_string(), proposal.shares.clone());
+        MusicSftEvent::SplitSheetUpdate(vec![SplitSheetLog {
+            token_class_id: token_class_id.to_string(),
+            account_id: proposal.proposer_id,
+            shares: proposal.shares,
+        }]).emit();
+    }
+
+    /// Proposes a new split sheet for a class. A pending proposal must be cancelled first
+    /// with `sft_cancel_split_proposal`. Shares are in basis points and must sum to 10000. Only current split holders can propose;
+    /// the proposer's consent is counted and the sheet applies once all current holders approve.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn sft_propose_split_sheet(&mut self, token_class_id: String, shares: Vec<SplitShare>) {
+        let proposer_id = self.assert_split_holder(&token_class_id);
+        // Leaves room for the seller in a payout
+        assert_valid_split_sheet(&shares, (self.max_payout_recipients - 1) as usize);
+        assert!(
+            !self.split_proposals.contains_key(&token_class_id),
+            "A split sheet proposal is already pending for this token class, cancel it first"
+        );
+        let initial_storage_usage = env::storage_usage();
+
+        self.split_proposals.insert(token_class_id.clone(), SplitProposal {
+            proposer_id: proposer_id.clone(),
+            shares: shares.clone(),
+            approved_by: vec![proposer_id.clone()],
+        });
+        MusicSftEvent::SplitSheetPropose(vec![SplitSheetLog {
+            token_class_id: token_class_id.clone(),
+            account_id: proposer_id,
+            shares,
+        }]).emit();
+        self.internal_try_apply_split_proposal(&token_class_id);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Consents to the pending split sheet proposal for a class. Only current split holders can approve.
+    /// Storage is charged to the predecessor's storage balance, topped up by the attached deposit.
+    #[payable]
+    pub fn sft_approve_split_sheet(&mut self, token_class_id: String) {
+        let approver_id = self.assert_split_holder(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let proposal = self.split_proposals.get_mut(&token_class_id)
+            .expect("No split sheet proposal for this token class");
+        assert!(!proposal.approved_by.contains(&approver_id), "Split sheet proposal already approved");
+        proposal.approved_by.push(approver_id.clone());
+        let shares = proposal.shares.clone();
+
+        MusicSftEvent::SplitSheetApprove(vec![SplitSheetLog {
+            token_class_id: token_class_id.clone(),
+            account_id: approver_id,
+            shares,
+        }]).emit();
+        self.internal_try_apply_split_proposal(&token_class_id);
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    /// Rejects the pending split sheet proposal for a class. Any current split holder can cancel it.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn sft_cancel_split_proposal(&mut self, token_class_id: String) {
+        assert_one_yocto();
+        let caller_id = self.assert_split_holder(&token_class_id);
+        let initial_storage_usage = env::storage_usage();
+        let proposer_id = self.split_proposals.get(&token_class_id)
+            .expect("No split sheet proposal for this token class")
+            .proposer_id
+            .clone();
+        let proposal = self.internal_remove_paid_by(Some(&proposer_id), |this| this.split_proposals.remove(&token_class_id).unwrap());
+
+        MusicSftEvent::SplitSheetCancel(vec![SplitSheetLog {
+            token_class_id,
+            account_id: caller_id,
+            shares: proposal.shares,
+        }]).emit();
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+    }
+
+    // Get the current split sheet for a token class
+    pub fn sft_split_sheet(&self, token_class_id: String) -> Vec<SplitShare> {
+        self.internal_split_sheet(&token_class_id)
+    }
+
+    // Get the pending split sheet proposal for a token class
+    pub fn sft_split_proposal(&self, token_class_id: String) -> Option<SplitProposal> {
+        self.split_proposals.get(&token_class_id).cloned()
+    }
+}
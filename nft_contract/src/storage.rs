@@ -0,0 +1,446 @@
+// --- NEP-145 Storage Management ---
+// Accounts prepay for the state they add with `storage_deposit`. Every call that changes the
+// contract's storage settles the net change against the caller's storage balance: added bytes
+// are debited, freed bytes are credited back. NEAR attached to a storage-paying call beyond
+// what the call itself costs (e.g. a purchase price) is added to the caller's storage balance
+// and can be taken out again with `storage_withdraw`.
+// An entry someone else paid for (a listing, a split proposal, a pending balance, copies sent to
+// another account) credits that payer when the caller removes it, not the caller. Callbacks and auction settlement cannot fail, so
+// they settle their storage against the account that caused it even past its balance; what it cannot
+// cover is owed, and its next storage-paying call fails until it tops up.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, NearToken, Promise};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub struct AccountStorage {
+    pub deposit: u128, // yoctoNEAR paid in and not withdrawn
+    pub used_bytes: u64, // Bytes charged to the account, its registration included
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+impl AccountStorage {
+    fn locked(&self) -> u128 {
+        u128::from(self.used_bytes) * env::storage_byte_cost().as_yoctonear()
+    }
+
+    fn to_storage_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.deposit),
+            available: U128(self.deposit.saturating_sub(self.locked())),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: records the bytes a registration takes, measured with the longest account ID
+    pub(crate) fn internal_measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id: AccountId = "a".repeat(64).parse().unwrap();
+        self.storage_accounts.insert(tmp_account_id.clone(), AccountStorage { deposit: 0, used_bytes: 0 });
+        self.storage_accounts.flush();
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.storage_accounts.remove(&tmp_account_id);
+        self.storage_accounts.flush();
+    }
+
+    // Internal helper: writes every cached collection change so `env::storage_usage` is accurate.
+    // LookupSets write through and need no flush.
+    pub(crate) fn internal_flush(&mut self) {
+        self.token_metadata.flush();
+        self.token_supply.flush();
+        self.legacy_owner_balances.flush();
+        self.token_classes.flush();
+        self.legacy_approvals.flush();
+        self.creators.flush();
+        self.max_supply.flush();
+        self.minted.flush();
+        self.class_minters.flush();
+        self.listings.flush();
+        self.listings_by_class.flush();
+        self.listings_by_seller.flush();
+        self.royalties.flush();
+        self.split_sheets.flush();
+        self.split_proposals.flush();
+        self.class_fee_totals.flush();
+        self.pending_withdrawals.flush();
+        self.ft_treasury_balances.flush();
+        self.ft_pending_withdrawals.flush();
+        self.accepted_ft_tokens.flush();
+        self.offers.flush();
+        self.offers_by_class.flush();
+        self.auctions.flush();
+        self.auctions_by_class.flush();
+        self.dutch_auctions.flush();
+        self.sale_windows.flush();
+        self.upcoming_drops.flush();
+        self.presales.flush();
+        self.presale_purchases.flush();
+        self.purchase_rules.flush();
+        self.storage_accounts.flush();
+        self.balances.flush();
+        self.classes_by_owner.flush();
+        self.approvals.flush();
+        self.legacy_owners.flush();
+        self.balance_storage_payers.flush();
+    }
+
+    // Internal helper: adds `deposit` to an account's storage balance, then settles the storage
+    // change since `initial_storage_usage` against it. Panics if the balance cannot cover it.
+    // An unregistered account with nothing to pay for gets its deposit back instead.
+    pub(crate) fn internal_charge_storage(&mut self, account_id: &AccountId, initial_storage_usage: u64, deposit: u128) {
+        self.internal_settle_storage(account_id, initial_storage_usage, deposit, true);
+    }
+
+    // Internal helper: `internal_charge_storage` for calls that cannot fail, i.e. callbacks and auction
+    // settlement. Growth the balance cannot cover is still charged and left owed.
+    pub(crate) fn internal_charge_storage_owed(&mut self, account_id: &AccountId, initial_storage_usage: u64, deposit: u128) {
+        self.internal_settle_storage(account_id, initial_storage_usage, deposit, false);
+    }
+
+    // Internal helper: runs `remove`, which drops entries `payer_id` paid for, and credits the bytes it
+    // frees to that payer instead of the calling account. The contract keeps them when there is no payer.
+    pub(crate) fn internal_remove_paid_by<R>(&mut self, payer_id: Option<&AccountId>, remove: impl FnOnce(&mut Self) -> R) -> R {
+        self.internal_flush();
+        let initial_storage_usage = env::storage_usage();
+        let result = remove(self);
+        self.internal_flush();
+        let freed_bytes = initial_storage_usage.saturating_sub(env::storage_usage());
+        if let Some(payer_id) = payer_id {
+            self.internal_release_storage(payer_id, freed_bytes);
+        }
+        self.released_storage_usage += freed_bytes;
+        result
+    }
+
+    // Internal helper shared by the charging helpers. Panics when `must_cover` is set and the balance falls short.
+    fn internal_settle_storage(&mut self, account_id: &AccountId, initial_storage_usage: u64, deposit: u128, must_cover: bool) {
+        self.internal_flush();
+        // Bytes already credited to the entries' payers are not the caller's to get back
+        let final_storage_usage = env::storage_usage() + std::mem::take(&mut self.released_storage_usage);
+
+        let mut account = match self.storage_accounts.get(account_id) {
+            Some(account) => account.clone(),
+            None if final_storage_usage <= initial_storage_usage => {
+                if deposit > 1 { // Don't refund dust
+                    Promise::new(account_id.clone()).transfer(NearToken::from_yoctonear(deposit));
+                }
+                return;
+            }
+            None => AccountStorage { deposit: 0, used_bytes: self.account_storage_usage },
+        };
+        account.deposit += deposit;
+        if final_storage_usage >= initial_storage_usage {
+            account.used_bytes += final_storage_usage - initial_storage_usage;
+        } else {
+            account.used_bytes = account.used_bytes
+                .saturating_sub(initial_storage_usage - final_storage_usage)
+                .max(self.account_storage_usage);
+        }
+
+        let required = account.locked();
+        if must_cover && account.deposit < required {
+            env::panic_str(&format!(
+                "Insufficient storage balance for {}. Required: {}, Deposited: {}. Top it up with storage_deposit",
+                account_id, required, account.deposit
+            ));
+        }
+        self.storage_accounts.insert(account_id.clone(), account);
+    }
+
+    // Internal helper: credits an account with bytes it paid for that another call has freed,
+    // e.g. the entry of an auction someone else settled. Unregistered accounts have nothing to credit.
+    pub(crate) fn internal_release_storage(&mut self, account_id: &AccountId, freed_bytes: u64) {
+        if let Some(account) = self.storage_accounts.get_mut(account_id) {
+            account.used_bytes = account.used_bytes
+                .saturating_sub(freed_bytes)
+                .max(self.account_storage_usage);
+        }
+    }
+
+    // Internal helper: settles a call's storage change against the predecessor, adding the attached deposit
+    pub(crate) fn internal_charge_predecessor_storage(&mut self, initial_storage_usage: u64) {
+        self.internal_charge_storage(
+            &env::predecessor_account_id(),
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    /// Adds the attached deposit to the storage balance of `account_id` (the predecessor by default),
+    /// registering it if needed. With `registration_only`, only the minimum is kept and the rest refunded.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount = env::attached_deposit().as_yoctonear();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        let mut refund = 0;
+        let account = match self.storage_accounts.get(&account_id).cloned() {
+            Some(mut account) => {
+                if registration_only {
+                    refund = amount;
+                } else {
+                    account.deposit += amount;
+                }
+                account
+            }
+            None => {
+                assert!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+                let deposit = if registration_only { min_balance } else { amount };
+                refund = amount - deposit;
+                AccountStorage { deposit, used_bytes: self.account_storage_usage }
+            }
+        };
+        self.storage_accounts.insert(account_id, account.clone());
+
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(NearToken::from_yoctonear(refund));
+        }
+        account.to_storage_balance()
+    }
+
+    /// Sends the predecessor part of its storage balance not locked by storage in use,
+    /// all of it when `amount` is omitted.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self.storage_accounts.get(&account_id).cloned()
+            .unwrap_or_else(|| env::panic_str(&format!("The account {} is not registered", account_id)));
+        let available = account.to_storage_balance().available.0;
+        let amount = amount.map(|amount| amount.0).unwrap_or(available);
+        assert!(amount <= available, "The amount is greater than the available storage balance");
+
+        if amount > 0 {
+            account.deposit -= amount;
+            self.storage_accounts.insert(account_id.clone(), account.clone());
+            Promise::new(account_id).transfer(NearToken::from_yoctonear(amount));
+        }
+        account.to_storage_balance()
+    }
+
+    /// Unregisters the predecessor and refunds its whole storage deposit. Only accounts that no longer
+    /// have storage charged to them beyond their registration can unregister; `force` is not supported.
+    /// Returns whether the account was registered.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert!(!force.unwrap_or(false), "Forced unregistration is not supported");
+        let account_id = env::predecessor_account_id();
+        let Some(account) = self.storage_accounts.get(&account_id).cloned() else {
+            return false;
+        };
+        assert!(
+            account.used_bytes <= self.account_storage_usage,
+            "Release the storage charged to this account before unregistering"
+        );
+
+        self.storage_accounts.remove(&account_id);
+        if account.deposit > 0 {
+            Promise::new(account_id).transfer(NearToken::from_yoctonear(account.deposit));
+        }
+        true
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(u128::from(self.account_storage_usage) * env::storage_byte_cost().as_yoctonear()),
+            max: None,
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts.get(&account_id).map(|account| account.to_storage_balance())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{account, set_callback_context, set_context, song_metadata};
+    use near_sdk::json_types::U64;
+    use near_sdk::PromiseResult;
+
+    // Every call below is followed by `internal_flush`, which happens on chain when the contract is written back
+    // at the end of the call. Otherwise the next call would be charged for the previous one's cached writes.
+
+    // A fresh contract where alice.near minted `amount` copies of "city-song" to herself with 1 NEAR attached
+    fn setup(amount: u64) -> Contract {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        set_context(&account("alice.near"), NearToken::from_near(1));
+        contract.sft_mint(SftMintArgs {
+            token_class_id: "city-song".to_string(),
+            amount: U64(amount),
+            receiver_id: account("alice.near"),
+            metadata: Some(song_metadata()),
+            max_supply: None,
+            royalty_bps: None,
+            dutch_auction: None,
+            sale_window: None,
+            presale: None,
+        });
+        contract.internal_flush();
+        contract
+    }
+
+    fn register(contract: &mut Contract, account_id: &str) {
+        set_context(&account(account_id), NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+        contract.internal_flush();
+    }
+
+    fn used_bytes(contract: &Contract, account_id: &str) -> u64 {
+        contract.storage_accounts.get(&account(account_id)).expect("Account not registered").used_bytes
+    }
+
+    fn transfer(contract: &mut Contract, sender_id: &str, receiver_id: &str, amount: u64) {
+        set_context(&account(sender_id), NearToken::from_yoctonear(1));
+        contract.sft_transfer(account(receiver_id), "city-song".to_string(), U64(amount), None);
+        contract.internal_flush();
+    }
+
+    #[test]
+    fn deposit_registers_and_keeps_the_rest() {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut contract = Contract::new(account("owner.near"));
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        register(&mut contract, "bob.near");
+        let balance = contract.storage_balance_of(account("bob.near")).unwrap();
+        assert_eq!(balance.total.0, NearToken::from_near(1).as_yoctonear());
+        assert_eq!(balance.available.0, NearToken::from_near(1).as_yoctonear() - min_balance);
+
+        // Registering again only refunds the deposit
+        set_context(&account("bob.near"), NearToken::from_near(1));
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, NearToken::from_near(1).as_yoctonear());
+    }
+
+    #[test]
+    fn storage_paying_calls_charge_the_caller() {
+        let contract = setup(5);
+        let used = used_bytes(&contract, "alice.near");
+        assert!(used > contract.account_storage_usage);
+        let balance = contract.storage_balance_of(account("alice.near")).unwrap();
+        assert_eq!(
+            balance.available.0,
+            NearToken::from_near(1).as_yoctonear() - u128::from(used) * env::storage_byte_cost().as_yoctonear()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage balance for carol.near")]
+    fn charge_fails_past_the_storage_balance() {
+        let mut contract = setup(5);
+        set_context(&account("carol.near"), NearToken::from_yoctonear(0));
+        contract.sft_mint(SftMintArgs {
+            token_class_id: "carol-song".to_string(),
+            amount: U64(1),
+            receiver_id: account("carol.near"),
+            metadata: Some(song_metadata()),
+            max_supply: None,
+            royalty_bps: None,
+            dutch_auction: None,
+            sale_window: None,
+            presale: None,
+        });
+    }
+
+    #[test]
+    fn release_keeps_the_registration_charged() {
+        let mut contract = setup(5);
+        contract.internal_release_storage(&account("alice.near"), u64::MAX);
+        assert_eq!(used_bytes(&contract, "alice.near"), contract.account_storage_usage);
+        // Unregistered accounts have nothing to credit
+        contract.internal_release_storage(&account("nobody.near"), 100);
+        assert!(contract.storage_balance_of(account("nobody.near")).is_none());
+    }
+
+    #[test]
+    fn emptied_balance_refunds_whoever_paid_for_it() {
+        let mut contract = setup(5);
+        register(&mut contract, "bob.near");
+        let alice_before = used_bytes(&contract, "alice.near");
+        transfer(&mut contract, "alice.near", "bob.near", 2);
+        let entry_bytes = used_bytes(&contract, "alice.near") - alice_before;
+        assert!(entry_bytes > 0);
+
+        // bob pays for eve's entry, while the storage of his own goes back to alice
+        let bob_before = used_bytes(&contract, "bob.near");
+        transfer(&mut contract, "bob.near", "eve.near", 2);
+        assert_eq!(used_bytes(&contract, "alice.near"), alice_before);
+        assert!(used_bytes(&contract, "bob.near") > bob_before);
+        assert_eq!(contract.sft_balance_of(account("eve.near"), "city-song".to_string()).0, 2);
+    }
+
+    #[test]
+    fn accepting_an_offer_charges_the_seller() {
+        let mut contract = setup(5);
+        set_context(&account("eve.near"), NearToken::from_near(1));
+        contract.market_make_offer("city-song".to_string(), U128(1_000), U64(2), None);
+        contract.internal_flush();
+        assert!(used_bytes(&contract, "eve.near") > contract.account_storage_usage);
+
+        let alice_before = used_bytes(&contract, "alice.near");
+        set_context(&account("alice.near"), NearToken::from_yoctonear(1));
+        contract.accept_offer("city-song".to_string(), account("eve.near"), None);
+        contract.internal_flush();
+
+        assert_eq!(contract.sft_balance_of(account("eve.near"), "city-song".to_string()).0, 2);
+        assert!(used_bytes(&contract, "alice.near") > alice_before);
+        // The filled offer's storage goes back to eve
+        assert_eq!(used_bytes(&contract, "eve.near"), contract.account_storage_usage);
+    }
+
+    #[test]
+    fn callback_growth_is_left_owed() {
+        let mut contract = setup(5);
+        set_context(&account("eve.near"), NearToken::from_near(1));
+        contract.market_make_offer("city-song".to_string(), U128(1_000), U64(2), None);
+        contract.internal_flush();
+        set_context(&account("alice.near"), NearToken::from_yoctonear(1));
+        contract.accept_offer("city-song".to_string(), account("eve.near"), None);
+        contract.internal_flush();
+
+        // alice withdraws her proceeds, freeing their ledger entry, and every NEAR not locked
+        set_context(&account("alice.near"), NearToken::from_yoctonear(1));
+        let _ = contract.withdraw(None);
+        contract.internal_flush();
+        set_context(&account("alice.near"), NearToken::from_yoctonear(1));
+        contract.storage_withdraw(None);
+        contract.internal_flush();
+
+        // The transfer fails, so the callback recreates the entry past her storage balance
+        set_callback_context(PromiseResult::Failed);
+        assert!(!contract.on_withdraw(account("alice.near"), U128(2_000)));
+        contract.internal_flush();
+        assert_eq!(contract.get_pending_withdrawal(account("alice.near")).0, 2_000);
+
+        let alice_storage = contract.storage_accounts.get(&account("alice.near")).unwrap().clone();
+        assert!(alice_storage.locked() > alice_storage.deposit);
+        assert_eq!(contract.storage_balance_of(account("alice.near")).unwrap().available.0, 0);
+    }
+}
@@ -0,0 +1,57 @@
+// --- Unit Test Helpers ---
+// Mocked-blockchain setup shared by the unit tests of every module.
+
+use near_sdk::json_types::U128;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{testing_env, AccountId, Gas, NearToken, PromiseResult, RuntimeFeesConfig};
+
+use crate::NFTMetadata;
+
+pub(crate) fn account(id: &str) -> AccountId {
+    id.parse().unwrap()
+}
+
+pub(crate) fn song_metadata() -> NFTMetadata {
+    NFTMetadata {
+        title: "City Song".to_string(),
+        description: "Limited edition".to_string(),
+        media: "https://example.com/city-song.mp3".to_string(),
+        media_hash: "hash".to_string(),
+        price_per_copy: U128(1_000),
+        cover_photo: "https://example.com/city-song.png".to_string(),
+    }
+}
+
+fn context_builder(predecessor_id: &AccountId, deposit: NearToken) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id(account("music.near"))
+        .predecessor_account_id(predecessor_id.clone())
+        .attached_deposit(deposit)
+        .prepaid_gas(Gas::from_tgas(300));
+    builder
+}
+
+// Starts a fresh call to music.near from `predecessor_id`; storage carries over between calls
+pub(crate) fn set_context(predecessor_id: &AccountId, deposit: NearToken) {
+    set_context_with_input(predecessor_id, deposit, Vec::new());
+}
+
+// Like `set_context`, with `input` as the raw input of the call
+pub(crate) fn set_context_with_input(predecessor_id: &AccountId, deposit: NearToken, input: Vec<u8>) {
+    let mut context = context_builder(predecessor_id, deposit).build();
+    context.input = input;
+    testing_env!(context);
+}
+
+// Starts a callback music.near makes to itself, seeing `promise_result` as the result of the call it follows
+pub(crate) fn set_callback_context(promise_result: PromiseResult) {
+    let context = context_builder(&account("music.near"), NearToken::from_yoctonear(0)).build();
+    testing_env!(
+        context,
+        near_sdk::test_vm_config(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![promise_result],
+    );
+}
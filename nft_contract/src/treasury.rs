@@ -0,0 +1,106 @@
+// --- Platform Fee & Treasury ---
+// Marketplace sales pay a fee in basis points to the platform. Fees accrue to an internal
+// treasury balance and the contract owner withdraws them, instead of a transfer per sale.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, NearToken, Promise, PromiseResult};
+
+use crate::*;
+
+pub const MAX_MARKET_FEE_BPS: u16 = 1_000;
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: panics unless the predecessor is the contract owner
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the contract owner can call this method"
+        );
+    }
+
+    // Internal helper: takes the platform fee out of a sale and books it to the treasury of the
+    // sale's currency, NEAR or the NEP-141 token `ft_token_id`. Returns the fee.
+    pub(crate) fn internal_collect_market_fee(&mut self, token_class_id: &str, price: u128, ft_token_id: Option<&AccountId>) -> u128 {
+        let fee = price * u128::from(self.market_fee_bps) / u128::from(BASIS_POINTS);
+        if fee == 0 {
+            return 0;
+        }
+        match ft_token_id {
+            Some(ft_token_id) => *self.ft_treasury_balances.entry(ft_token_id.clone()).or_insert(0) += fee,
+            None => self.treasury_balance += fee,
+        }
+        *self.class_fee_totals
+            .entry((token_class_id.to_string(), ft_token_id.cloned()))
+            .or_insert(0) += fee;
+        fee
+    }
+
+    /// Sets the marketplace fee in basis points. Only the contract owner can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn set_market_fee(&mut self, fee_bps: u16) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(fee_bps <= MAX_MARKET_FEE_BPS, "Market fee cannot exceed {} basis points", MAX_MARKET_FEE_BPS);
+        self.market_fee_bps = fee_bps;
+
+        MusicSftEvent::MarketFeeUpdate(vec![MarketFeeUpdateLog { fee_bps }]).emit();
+    }
+
+    /// Sends accrued fees from the treasury, all of them when `amount` is omitted.
+    /// Only the contract owner can call this; funds go to the owner unless `receiver_id` is given.
+    /// If the transfer fails the callback puts the fees back in the treasury.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn withdraw_treasury(&mut self, amount: Option<U128>, receiver_id: Option<AccountId>) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let amount = amount.map(|amount| amount.0).unwrap_or(self.treasury_balance);
+        assert!(amount > 0, "Nothing to withdraw");
+        assert!(amount <= self.treasury_balance, "Amount exceeds the treasury balance");
+        let receiver_id = receiver_id.unwrap_or_else(|| self.owner_id.clone());
+
+        self.treasury_balance -= amount;
+        Promise::new(receiver_id.clone())
+            .transfer(NearToken::from_yoctonear(amount))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                    .on_withdraw_treasury(receiver_id, U128(amount)),
+            )
+    }
+
+    /// Callback after `withdraw_treasury`: restores the treasury balance if the transfer failed.
+    /// Returns whether the funds were delivered.
+    #[private]
+    pub fn on_withdraw_treasury(&mut self, receiver_id: AccountId, amount: U128) -> bool {
+        let delivered = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if delivered {
+            MusicSftEvent::TreasuryWithdraw(vec![TreasuryWithdrawLog {
+                receiver_id,
+                amount,
+                ft_token_id: None,
+            }]).emit();
+        } else {
+            self.treasury_balance += amount.0;
+        }
+        delivered
+    }
+
+    // Get the marketplace fee in basis points
+    pub fn get_market_fee_bps(&self) -> u16 {
+        self.market_fee_bps
+    }
+
+    // Get the fees accrued and not yet withdrawn
+    pub fn get_treasury_balance(&self) -> U128 {
+        U128(self.treasury_balance)
+    }
+
+    // Get the total fees ever collected on sales of a token class, in NEAR or in the token `ft_token_id`
+    pub fn get_class_fee_total(&self, token_class_id: String, ft_token_id: Option<AccountId>) -> U128 {
+        U128(self.class_fee_totals.get(&(token_class_id, ft_token_id)).copied().unwrap_or(0))
+    }
+}
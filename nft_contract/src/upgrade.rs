@@ -0,0 +1,423 @@
+// --- State Versions & Upgrades ---
+// The contract state is stored untagged under "STATE", so every layout it has been deployed with
+// is kept here and tagged through `VersionedContract`. The borsh tag of the current layout is
+// written under a separate key, and stored state reads back as `tag ++ STATE`. State written
+// before the tag existed is one of the two original layouts and is told apart by its shape.
+// `upgrade` deploys new code and calls `migrate`, which loads whatever layout is stored and
+// converts it to the current one.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, near_bindgen, AccountId, Gas, GasWeight, NearToken, Promise};
+use std::collections::HashMap;
+
+use crate::*;
+
+// Holds the borsh tag of the `VersionedContract` variant the stored state uses
+pub const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+// Borsh tag of `VersionedContract::V3`
+pub const CURRENT_STATE_VERSION: u8 = 2;
+
+/// The original layout (`lib.rs` as first deployed).
+#[derive(BorshDeserialize, BorshSerialize)]
+#[allow(deprecated)]
+pub struct ContractV1 {
+    pub owner_id: AccountId,
+    pub token_metadata: LookupMap<String, NFTMetadata>,
+    pub token_supply: LookupMap<String, u64>,
+    pub owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
+    pub token_classes: UnorderedSet<String>,
+    pub approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
+    pub creators: LookupMap<String, AccountId>,
+}
+
+/// The `updated_lib.rs` layout, which added a set of every account that received copies.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[allow(deprecated)]
+pub struct ContractV2 {
+    pub owner_id: AccountId,
+    pub token_metadata: LookupMap<String, NFTMetadata>,
+    pub token_supply: LookupMap<String, u64>,
+    pub owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
+    pub token_classes: UnorderedSet<String>,
+    pub approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
+    pub creators: LookupMap<String, AccountId>,
+    pub owner_classes: UnorderedSet<AccountId>,
+}
+
+/// The layout that moved balances into flat per-account maps and added the marketplace, sales and storage accounting.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[allow(deprecated)]
+pub struct ContractV3 {
+    pub owner_id: AccountId,
+    pub token_metadata: LookupMap<String, NFTMetadata>,
+    pub token_supply: LookupMap<String, u64>,
+    pub legacy_owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
+    pub token_classes: UnorderedSet<String>,
+    pub legacy_approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
+    pub creators: LookupMap<String, AccountId>,
+    pub retired_classes: LookupSet<String>,
+    pub max_supply: LookupMap<String, u64>,
+    pub primary_sale_classes: LookupSet<String>,
+    pub minted: LookupMap<String, u64>,
+    pub class_minters: LookupMap<String, Vec<AccountId>>,
+    pub listings: LookupMap<(AccountId, String), Listing>,
+    pub listings_by_class: LookupMap<String, IterableSet<AccountId>>,
+    pub listings_by_seller: LookupMap<AccountId, IterableSet<String>>,
+    pub royalties: LookupMap<String, u16>,
+    pub split_sheets: LookupMap<String, Vec<SplitShare>>,
+    pub split_proposals: LookupMap<String, SplitProposal>,
+    pub max_payout_recipients: u32,
+    pub market_fee_bps: u16,
+    pub treasury_balance: u128,
+    pub class_fee_totals: LookupMap<(String, Option<AccountId>), u128>,
+    pub pending_withdrawals: LookupMap<AccountId, PendingBalance>,
+    pub ft_treasury_balances: LookupMap<AccountId, u128>,
+    pub ft_pending_withdrawals: LookupMap<(AccountId, AccountId), PendingBalance>,
+    pub accepted_ft_tokens: IterableSet<AccountId>,
+    pub offers: LookupMap<(AccountId, String), Offer>,
+    pub offers_by_class: LookupMap<String, IterableSet<AccountId>>,
+    pub auctions: LookupMap<u64, Auction>,
+    pub auctions_by_class: LookupMap<String, IterableSet<u64>>,
+    pub next_auction_id: u64,
+    pub dutch_auctions: LookupMap<String, DutchAuction>,
+    pub sale_windows: LookupMap<String, SaleWindow>,
+    pub upcoming_drops: TreeMap<(u64, String), ()>,
+    pub presales: LookupMap<String, Presale>,
+    pub presale_allowlist: LookupSet<(String, AccountId)>,
+    pub presale_purchases: LookupMap<(String, AccountId), u64>,
+    pub purchase_rules: LookupMap<String, Vec<PurchaseRule>>,
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>,
+    pub account_storage_usage: u64,
+    pub balances: LookupMap<(AccountId, String), u64>,
+    pub classes_by_owner: LookupMap<AccountId, IterableSet<String>>,
+    pub approvals: LookupMap<(AccountId, String, AccountId), u64>,
+    pub legacy_owners: UnorderedSet<AccountId>,
+    pub balance_storage_payers: LookupMap<(AccountId, String), AccountId>,
+}
+
+/// Every layout the contract state has been stored in, oldest first.
+/// Append a variant (and bump `CURRENT_STATE_VERSION`) whenever `Contract` changes shape.
+#[derive(BorshDeserialize, BorshSerialize)]
+#[allow(clippy::large_enum_variant)] // Only built once per migration
+pub enum VersionedContract {
+    V1(ContractV1),
+    V2(ContractV2),
+    V3(ContractV3),
+}
+
+// Records that the stored state uses the current layout
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[CURRENT_STATE_VERSION]);
+}
+
+// Reads the stored state in whatever layout it was written with
+fn read_versioned_state() -> VersionedContract {
+    let state = env::storage_read(b"STATE").unwrap_or_else(|| env::panic_str("Contract is not initialized"));
+    if let Some(version) = env::storage_read(STATE_VERSION_KEY) {
+        return borsh::from_slice(&[version, state].concat())
+            .unwrap_or_else(|_| env::panic_str("Cannot read the contract state in its recorded version"));
+    }
+    // Untagged state: borsh reads must consume every byte, so only the matching layout decodes
+    if let Ok(state) = ContractV2::try_from_slice(&state) {
+        return VersionedContract::V2(state);
+    }
+    ContractV1::try_from_slice(&state)
+        .map(VersionedContract::V1)
+        .unwrap_or_else(|_| env::panic_str("Cannot read the contract state in any known version"))
+}
+
+impl Contract {
+    // Internal helper: the current layout around the collections kept from the original layouts.
+    // Balances and approvals stay in their legacy maps and move over account by account.
+    #[allow(deprecated)]
+    fn from_original_layout(
+        owner_id: AccountId,
+        token_metadata: LookupMap<String, NFTMetadata>,
+        token_supply: LookupMap<String, u64>,
+        owner_balances: LookupMap<AccountId, HashMap<String, u64>>,
+        token_classes: UnorderedSet<String>,
+        approvals: LookupMap<AccountId, HashMap<String, HashMap<AccountId, u64>>>,
+        creators: LookupMap<String, AccountId>,
+    ) -> Self {
+        let mut this = Self::new(owner_id);
+        this.token_metadata = token_metadata;
+        this.token_supply = token_supply;
+        this.legacy_owner_balances = owner_balances;
+        this.token_classes = token_classes;
+        this.legacy_approvals = approvals;
+        this.creators = creators;
+        this
+    }
+
+    // Internal helper: the current layout from the V3 one, which it matches field for field
+    fn from_v3(old: ContractV3) -> Self {
+        Self {
+            owner_id: old.owner_id,
+            token_metadata: old.token_metadata,
+            token_supply: old.token_supply,
+            legacy_owner_balances: old.legacy_owner_balances,
+            token_classes: old.token_classes,
+            legacy_approvals: old.legacy_approvals,
+            creators: old.creators,
+            retired_classes: old.retired_classes,
+            max_supply: old.max_supply,
+            primary_sale_classes: old.primary_sale_classes,
+            minted: old.minted,
+            class_minters: old.class_minters,
+            listings: old.listings,
+            listings_by_class: old.listings_by_class,
+            listings_by_seller: old.listings_by_seller,
+            royalties: old.royalties,
+            split_sheets: old.split_sheets,
+            split_proposals: old.split_proposals,
+            max_payout_recipients: old.max_payout_recipients,
+            market_fee_bps: old.market_fee_bps,
+            treasury_balance: old.treasury_balance,
+            class_fee_totals: old.class_fee_totals,
+            pending_withdrawals: old.pending_withdrawals,
+            ft_treasury_balances: old.ft_treasury_balances,
+            ft_pending_withdrawals: old.ft_pending_withdrawals,
+            accepted_ft_tokens: old.accepted_ft_tokens,
+            offers: old.offers,
+            offers_by_class: old.offers_by_class,
+            auctions: old.auctions,
+            auctions_by_class: old.auctions_by_class,
+            next_auction_id: old.next_auction_id,
+            dutch_auctions: old.dutch_auctions,
+            sale_windows: old.sale_windows,
+            upcoming_drops: old.upcoming_drops,
+            presales: old.presales,
+            presale_allowlist: old.presale_allowlist,
+            presale_purchases: old.presale_purchases,
+            purchase_rules: old.purchase_rules,
+            storage_accounts: old.storage_accounts,
+            account_storage_usage: old.account_storage_usage,
+            balances: old.balances,
+            classes_by_owner: old.classes_by_owner,
+            approvals: old.approvals,
+            legacy_owners: old.legacy_owners,
+            balance_storage_payers: old.balance_storage_payers,
+            released_storage_usage: 0,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Converts the stored state to the current layout. Called by `upgrade` right after new code is deployed.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let contract = match read_versioned_state() {
+            VersionedContract::V1(old) => Self::from_original_layout(
+                old.owner_id,
+                old.token_metadata,
+                old.token_supply,
+                old.owner_balances,
+                old.token_classes,
+                old.approvals,
+                old.creators,
+            ),
+            VersionedContract::V2(old) => {
+                let mut contract = Self::from_original_layout(
+                    old.owner_id,
+                    old.token_metadata,
+                    old.token_supply,
+                    old.owner_balances,
+                    old.token_classes,
+                    old.approvals,
+                    old.creators,
+                );
+                // Its owner set lists exactly the accounts whose balances still need moving
+                contract.legacy_owners = old.owner_classes;
+                contract
+            }
+            VersionedContract::V3(old) => Self::from_v3(old),
+        };
+        write_state_version();
+        contract
+    }
+
+    /// Deploys the contract code passed as raw input, then calls `migrate` on it with the remaining gas.
+    /// Only the contract owner can call this.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn upgrade(&mut self) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        let code = env::input().unwrap_or_else(|| env::panic_str("Contract code must be passed as input"));
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call_weight(
+                "migrate".to_string(),
+                Vec::new(),
+                NearToken::from_yoctonear(0),
+                Gas::from_gas(0),
+                GasWeight(1),
+            )
+            .as_return()
+    }
+
+    // Get the borsh tag of the layout the stored state uses
+    pub fn state_version(&self) -> u8 {
+        env::storage_read(STATE_VERSION_KEY).map_or(CURRENT_STATE_VERSION, |version| version[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::U64;
+    use crate::test_utils::{account, set_context, set_context_with_input, song_metadata};
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::get_created_receipts;
+
+    // Writes original-layout state: "city-song" minted by alice, 3 copies sold to bob,
+    // fan.near approved for 2 of alice's copies, and the old market's approval of the contract for 5
+    #[allow(deprecated)]
+    fn write_original_state(with_owner_set: bool) {
+        set_context(&account("owner.near"), NearToken::from_yoctonear(0));
+        let mut token_metadata = LookupMap::new(StorageKey::TokenMetadata);
+        token_metadata.insert("city-song".to_string(), song_metadata());
+        let mut token_supply = LookupMap::new(StorageKey::TokenSupply);
+        token_supply.insert("city-song".to_string(), 10);
+        let mut owner_balances = LookupMap::new(StorageKey::OwnerBalances);
+        owner_balances.insert(account("alice.near"), HashMap::from([("city-song".to_string(), 7)]));
+        owner_balances.insert(account("bob.near"), HashMap::from([("city-song".to_string(), 3)]));
+        let mut token_classes = UnorderedSet::new(StorageKey::TokenClasses);
+        token_classes.insert("city-song".to_string());
+        let mut approvals = LookupMap::new(StorageKey::Approvals);
+        approvals.insert(
+            account("alice.near"),
+            HashMap::from([(
+                "city-song".to_string(),
+                HashMap::from([(account("fan.near"), 2), (account("music.near"), 5)]),
+            )]),
+        );
+        let mut creators = LookupMap::new(StorageKey::CreatorMap);
+        creators.insert("city-song".to_string(), account("alice.near"));
+
+        if with_owner_set {
+            // `updated_lib.rs` kept its owner set under the seventh storage key
+            let mut owner_classes = UnorderedSet::new(StorageKey::OwnerClasses);
+            owner_classes.insert(account("alice.near"));
+            owner_classes.insert(account("bob.near"));
+            env::state_write(&ContractV2 {
+                owner_id: account("owner.near"),
+                token_metadata,
+                token_supply,
+                owner_balances,
+                token_classes,
+                approvals,
+                creators,
+                owner_classes,
+            });
+        } else {
+            env::state_write(&ContractV1 {
+                owner_id: account("owner.near"),
+                token_metadata,
+                token_supply,
+                owner_balances,
+                token_classes,
+                approvals,
+                creators,
+            });
+        }
+    }
+
+    // Runs `migrate` the way the runtime does and stores its result
+    fn migrate() -> Contract {
+        set_context(&account("music.near"), NearToken::from_yoctonear(0));
+        let contract = Contract::migrate();
+        env::state_write(&contract);
+        contract
+    }
+
+    fn assert_original_state_kept(contract: &Contract) {
+        assert_eq!(contract.owner_id, account("owner.near"));
+        assert_eq!(contract.state_version(), CURRENT_STATE_VERSION);
+        assert_eq!(contract.sft_metadata("city-song".to_string()).unwrap().title, "City Song");
+        assert_eq!(contract.sft_total_supply("city-song".to_string()).0, 10);
+        assert_eq!(contract.sft_balance_of(account("alice.near"), "city-song".to_string()).0, 7);
+        assert_eq!(contract.sft_balance_of(account("bob.near"), "city-song".to_string()).0, 3);
+        assert_eq!(contract.sft_is_approved(account("alice.near"), account("fan.near"), "city-song".to_string()).0, 2);
+        assert_eq!(contract.sft_is_approved(account("alice.near"), account("music.near"), "city-song".to_string()).0, 0);
+        let classes = contract.sft_get_all_metadata(None, None);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].creator_id, account("alice.near"));
+        assert!(contract.storage_balance_bounds().min.0 > 0);
+    }
+
+    #[test]
+    fn migrates_original_layout() {
+        write_original_state(false);
+        let mut contract = migrate();
+        assert_original_state_kept(&contract);
+        assert!(contract.legacy_owners(None, None).is_empty());
+
+        // Legacy balances move over on the first transfer
+        set_context(&account("alice.near"), NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+        set_context(&account("alice.near"), NearToken::from_yoctonear(1));
+        contract.sft_transfer(account("carol.near"), "city-song".to_string(), U64(4), None);
+        assert!(!contract.is_legacy_account(account("alice.near")));
+        assert_eq!(contract.sft_balance_of(account("alice.near"), "city-song".to_string()).0, 3);
+        assert_eq!(contract.sft_balance_of(account("carol.near"), "city-song".to_string()).0, 4);
+        assert_eq!(contract.sft_is_approved(account("alice.near"), account("fan.near"), "city-song".to_string()).0, 2);
+        assert!(contract.approvals.get(&(account("alice.near"), "city-song".to_string(), account("music.near"))).is_none());
+    }
+
+    #[test]
+    fn migrates_updated_layout() {
+        write_original_state(true);
+        let mut contract = migrate();
+        assert_original_state_kept(&contract);
+
+        let mut legacy_owners = contract.legacy_owners(None, None);
+        legacy_owners.sort();
+        assert_eq!(legacy_owners, vec![account("alice.near"), account("bob.near")]);
+
+        set_context(&account("owner.near"), NearToken::from_near(1));
+        assert_eq!(contract.migrate_legacy_accounts(legacy_owners, None), 2);
+        assert!(contract.legacy_owners(None, None).is_empty());
+        assert_eq!(contract.sft_classes_of_owner(account("bob.near"), None, None), vec!["city-song".to_string()]);
+        assert_eq!(contract.sft_balance_of(account("bob.near"), "city-song".to_string()).0, 3);
+    }
+
+    #[test]
+    fn migrating_current_layout_keeps_state() {
+        write_original_state(false);
+        migrate();
+        let contract = migrate();
+        assert_original_state_kept(&contract);
+    }
+
+    #[test]
+    fn upgrade_deploys_code_and_calls_migrate() {
+        write_original_state(false);
+        let mut contract = migrate();
+        let code = b"new contract code".to_vec();
+        set_context_with_input(&account("owner.near"), NearToken::from_yoctonear(1), code.clone());
+        let _ = contract.upgrade();
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, account("music.near"));
+        assert!(receipts[0].actions.iter().any(|action| matches!(
+            action, MockAction::DeployContract { code: deployed, .. } if *deployed == code
+        )));
+        assert!(receipts[0].actions.iter().any(|action| matches!(
+            action, MockAction::FunctionCallWeight { method_name, .. } if method_name == b"migrate"
+        )));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract owner can call this method")]
+    fn upgrade_requires_owner() {
+        write_original_state(false);
+        let mut contract = migrate();
+        set_context_with_input(&account("alice.near"), NearToken::from_yoctonear(1), b"code".to_vec());
+        let _ = contract.upgrade();
+    }
+}
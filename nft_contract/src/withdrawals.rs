@@ -0,0 +1,115 @@
+// --- Pending Withdrawals ---
+// Sale proceeds are credited to an internal ledger instead of being pushed with a transfer
+// per sale. Accounts pull their balance with `withdraw`; if the transfer fails (e.g. the
+// account was deleted) the callback puts the funds back on the ledger. Each ledger entry
+// remembers whose storage paid for it, and that account gets the storage back once it is withdrawn.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Gas, NearToken, Promise, PromiseResult};
+
+use crate::*;
+
+pub const GAS_FOR_WITHDRAW_CALLBACK: Gas = Gas::from_tgas(5);
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub struct PendingBalance {
+    pub amount: u128,
+    pub storage_payer: Option<AccountId>, // Paid for the entry and gets its storage back; the contract when unset
+}
+
+#[near_bindgen]
+impl Contract {
+    // Internal helper: books proceeds for each account and logs them in a single event.
+    // New ledger entries are paid for by `storage_payer`, or by the contract when it is None.
+    pub(crate) fn internal_credit_all(
+        &mut self,
+        token_class_id: &str,
+        credits: Vec<(AccountId, u128)>,
+        storage_payer: Option<&AccountId>,
+    ) {
+        let mut credit_logs = Vec::with_capacity(credits.len());
+        for (account_id, amount) in credits {
+            if amount == 0 {
+                continue;
+            }
+            self.pending_withdrawals
+                .entry(account_id.clone())
+                .or_insert_with(|| PendingBalance { amount: 0, storage_payer: storage_payer.cloned() })
+                .amount += amount;
+            credit_logs.push(BalanceCreditLog {
+                account_id,
+                token_class_id: Some(token_class_id.to_string()),
+                amount: U128(amount),
+                ft_token_id: None,
+            });
+        }
+        if !credit_logs.is_empty() {
+            MusicSftEvent::BalanceCredit(credit_logs).emit();
+        }
+    }
+
+    /// Sends the predecessor's claimable proceeds, all of them when `amount` is omitted.
+    /// Withdrawing everything gives the ledger entry's storage back to whoever paid for it.
+    /// Requires exactly 1 yoctoNEAR attached for security.
+    #[payable]
+    pub fn withdraw(&mut self, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let pending = self.pending_withdrawals.get(&account_id).cloned()
+            .unwrap_or(PendingBalance { amount: 0, storage_payer: None });
+        let amount = amount.map(|amount| amount.0).unwrap_or(pending.amount);
+        assert!(amount > 0, "Nothing to withdraw");
+        assert!(amount <= pending.amount, "Amount exceeds the pending balance");
+
+        if pending.amount == amount {
+            self.internal_remove_paid_by(pending.storage_payer.as_ref(), |this| {
+                this.pending_withdrawals.remove(&account_id);
+            });
+        } else {
+            self.pending_withdrawals.insert(
+                account_id.clone(),
+                PendingBalance { amount: pending.amount - amount, ..pending },
+            );
+        }
+
+        self.internal_charge_predecessor_storage(initial_storage_usage);
+        Promise::new(account_id.clone())
+            .transfer(NearToken::from_yoctonear(amount))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_WITHDRAW_CALLBACK)
+                    .on_withdraw(account_id, U128(amount)),
+            )
+    }
+
+    /// Callback after `withdraw`: restores the pending balance if the transfer failed.
+    /// A ledger entry it has to recreate is charged to the account itself.
+    /// Returns whether the funds were delivered.
+    #[private]
+    pub fn on_withdraw(&mut self, account_id: AccountId, amount: U128) -> bool {
+        let delivered = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if delivered {
+            MusicSftEvent::Withdraw(vec![BalanceCreditLog {
+                account_id,
+                token_class_id: None,
+                amount,
+                ft_token_id: None,
+            }]).emit();
+        } else {
+            let initial_storage_usage = env::storage_usage();
+            self.pending_withdrawals
+                .entry(account_id.clone())
+                .or_insert_with(|| PendingBalance { amount: 0, storage_payer: Some(account_id.clone()) })
+                .amount += amount.0;
+            self.internal_charge_storage_owed(&account_id, initial_storage_usage, 0);
+        }
+        delivered
+    }
+
+    // Get the proceeds an account can withdraw
+    pub fn get_pending_withdrawal(&self, account_id: AccountId) -> U128 {
+        U128(self.pending_withdrawals.get(&account_id).map(|pending| pending.amount).unwrap_or(0))
+    }
+}
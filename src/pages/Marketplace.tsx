@@ -8,11 +8,9 @@ import { useWalletSelector } from '../contexts/WalletSelectorContext';
 import {CopyToClipboard} from 'react-copy-to-clipboard';
 import { 
     getAllSftMetadata, 
-    getMarketApprovedSellers, 
+    getListingsForClass, 
     marketBuySft,
-    getAllOwners,
-    TokenClassMetadata,
-    CONTRACT_NAME
+    TokenClassMetadata
     // Remove unused sftBalanceOf 
     // sftBalanceOf 
 } from '../services/near';
@@ -81,7 +79,7 @@ const Marketplace: React.FC = () => {
     }, [])
 
     // Function to handle the buy action
-    const handleBuy = async (token_class_id: string, price: string) => {
+    const handleBuy = async (token_class_id: string, _price: string) => {
         // Check selector first
          if (!selector || !accountId) {
             setError("Please connect your wallet first.");
@@ -104,29 +102,31 @@ const Marketplace: React.FC = () => {
 
         try {
              // --- Find a seller --- 
-             // TODO: Implement proper seller discovery mechanism.
-             // The current `getMarketApprovedSellers` requires knowing potential seller IDs upfront.
-             // This workaround assumes the contract deployer (CONTRACT_NAME) is the only seller.
-            const contractOwner = CONTRACT_NAME;
-            const allSellers = await getAllOwners(near);
-            const potentialSellerIds = [contractOwner, ...allSellers]; 
-
-            console.log(`Checking potential sellers for ${token_class_id}:`, potentialSellerIds);
-            // Ensure near is passed correctly here
-            const approvedSellersMap = await getMarketApprovedSellers(near, token_class_id, potentialSellerIds);
-            const approvedSellers = Object.keys(approvedSellersMap);
-            console.log(`Approved sellers found:`, approvedSellersMap);
-
-            if (approvedSellers.length === 0) {
-                 throw new Error("No sellers found who have approved the marketplace for this item (checked contract owner)."); // Updated error message
+             // Pick the cheapest live NEAR-priced listing of someone else's
+            const nowNs = BigInt(Date.now()) * 1_000_000n;
+            const listings = (await getListingsForClass(near, token_class_id)).filter(listing =>
+                listing.seller_id !== accountId &&
+                listing.ft_token_id === null &&
+                BigInt(listing.quantity) > 0n &&
+                (listing.expires_at === null || BigInt(listing.expires_at) > nowNs)
+            );
+            console.log(`Live listings found for ${token_class_id}:`, listings);
+
+            if (listings.length === 0) {
+                 throw new Error("No copies of this item are listed for sale right now.");
             }
-            const seller_id = approvedSellers[0]; // Pick first available
+            const listing = listings.reduce((cheapest, candidate) =>
+                BigInt(candidate.price_per_copy) < BigInt(cheapest.price_per_copy) ? candidate : cheapest
+            );
+            const seller_id = listing.seller_id;
+            // The listing's price is what the contract charges, not the class's primary price
+            const listingPrice = listing.price_per_copy;
             
-            console.log(`Attempting to buy ${token_class_id} from seller ${seller_id} for price ${price} yoctoNEAR`);
+            console.log(`Attempting to buy ${token_class_id} from seller ${seller_id} for price ${listingPrice} yoctoNEAR`);
 
             // --- Execute the purchase ---
             // Use the correct Wallet object and remove casting
-            await marketBuySft(wallet, token_class_id, seller_id, price, dispatch, changeAlertContent);
+            await marketBuySft(wallet, token_class_id, seller_id, listingPrice, dispatch, changeAlertContent);
 
             alert(`Successfully purchased 1 copy of ${token_class_id} from ${seller_id}!`);
             // TODO: Maybe refetch user balance or inventory here
@@ -1,7 +1,7 @@
 import React, { useState, useEffect, useCallback } from 'react';
 import { useWalletSelector } from '../contexts/WalletSelectorContext';
-import { sftInventoryOfOwner, sftApproveMarketplace, NFTMetadata, getAllSftMetadata } from '../services/near';
-import { CONTRACT_NAME } from '../services/near'; // Import contract name for approval target
+import { sftInventoryOfOwner, marketListSft, NFTMetadata, getAllSftMetadata } from '../services/near';
+import { utils } from 'near-api-js'; // For converting NEAR prices to yoctoNEAR
 import type { Wallet } from '@near-wallet-selector/core';
 
 // Interface for combined inventory item with metadata
@@ -17,8 +17,9 @@ const MyNFTs: React.FC = () => {
     const [allMetadata, setAllMetadata] = useState<Record<string, NFTMetadata>>({});
     const [loading, setLoading] = useState<boolean>(true);
     const [error, setError] = useState<string | null>(null);
-    const [approvalAmounts, setApprovalAmounts] = useState<Record<string, string>>({}); // Track input values
-    const [approvingClassId, setApprovingClassId] = useState<string | null>(null);
+    const [listAmounts, setListAmounts] = useState<Record<string, string>>({}); // Track input values
+    const [listPrices, setListPrices] = useState<Record<string, string>>({}); // NEAR per copy, as typed
+    const [listingClassId, setListingClassId] = useState<string | null>(null);
 
     // Fetch all metadata once
     const fetchAllMetadata = useCallback(async () => {
@@ -73,13 +74,19 @@ const MyNFTs: React.FC = () => {
         }
     }, [fetchInventory, allMetadata, near]); // Depend on fetchInventory and allMetadata
 
-    const handleApprovalChange = (classId: string, value: string) => {
+    const handleAmountChange = (classId: string, value: string) => {
         // Allow only non-negative integers
         const sanitizedValue = value.replace(/[^0-9]/g, '');
-        setApprovalAmounts(prev => ({ ...prev, [classId]: sanitizedValue }));
+        setListAmounts(prev => ({ ...prev, [classId]: sanitizedValue }));
     };
 
-    const handleApprove = async (classId: string) => {
+    const handlePriceChange = (classId: string, value: string) => {
+        // Allow only non-negative decimals
+        const sanitizedValue = value.replace(/[^0-9.]/g, '');
+        setListPrices(prev => ({ ...prev, [classId]: sanitizedValue }));
+    };
+
+    const handleList = async (classId: string) => {
         if (!selector || !accountId) {
             setError("Please connect your wallet first.");
             return;
@@ -90,30 +97,39 @@ const MyNFTs: React.FC = () => {
             return;
         }
 
-        const amount = approvalAmounts[classId];
+        const amount = listAmounts[classId];
         if (!amount || parseInt(amount, 10) <= 0) {
-            setError("Please enter a valid positive amount to approve.");
+            setError("Please enter a valid positive amount to list.");
             return;
         }
 
         const item = inventory.find(i => i.token_class_id === classId);
         if (item && parseInt(amount, 10) > parseInt(item.balance, 10)) {
-             setError(`Approval amount (${amount}) cannot exceed your balance (${item.balance}).`);
+             setError(`Listing amount (${amount}) cannot exceed your balance (${item.balance}).`);
              return;
         }
 
-        setApprovingClassId(classId);
+        // Without a typed price the copies are listed at the class's primary price
+        const typedPrice = listPrices[classId];
+        const pricePerCopy = typedPrice ? utils.format.parseNearAmount(typedPrice) : item?.metadata?.price_per_copy;
+        if (!pricePerCopy || BigInt(pricePerCopy) <= 0n) {
+            setError("Please enter a valid positive price per copy.");
+            return;
+        }
+
+        setListingClassId(classId);
         setError(null);
         try {
-            console.log(`Approving ${amount} of ${classId} for marketplace ${CONTRACT_NAME}`);
-            await sftApproveMarketplace(wallet, classId, amount);
-            alert(`Successfully approved ${amount} copies of ${classId} for sale.`);
-            setApprovalAmounts(prev => ({ ...prev, [classId]: '' })); // Clear input
+            console.log(`Listing ${amount} of ${classId} at ${pricePerCopy} yoctoNEAR per copy`);
+            await marketListSft(wallet, classId, pricePerCopy, amount);
+            alert(`Successfully listed ${amount} copies of ${classId} for sale.`);
+            setListAmounts(prev => ({ ...prev, [classId]: '' })); // Clear inputs
+            setListPrices(prev => ({ ...prev, [classId]: '' }));
         } catch (err: any) {
-            console.error("Failed to approve:", err);
-            setError(`Approval failed: ${err.message || err}`);
+            console.error("Failed to list:", err);
+            setError(`Listing failed: ${err.message || err}`);
         } finally {
-            setApprovingClassId(null);
+            setListingClassId(null);
         }
     };
 
@@ -149,27 +165,36 @@ const MyNFTs: React.FC = () => {
                             <p className="text-gray-800 font-medium mb-4">Your Balance: {item.balance}</p>
 
                             <div className="mt-auto pt-4 border-t">
-                                 <label htmlFor={`approve-${item.token_class_id}`} className="block text-sm font-medium text-gray-700 mb-1">
-                                     Approve for Sale:
+                                 <label htmlFor={`list-${item.token_class_id}`} className="block text-sm font-medium text-gray-700 mb-1">
+                                     List for Sale:
                                  </label>
                                 <div className="flex items-center space-x-2">
                                     <input
                                         type="number"
-                                        id={`approve-${item.token_class_id}`}
+                                        id={`list-${item.token_class_id}`}
                                         min="1"
                                         step="1"
-                                        value={approvalAmounts[item.token_class_id] || ''}
-                                        onChange={(e) => handleApprovalChange(item.token_class_id, e.target.value)}
+                                        value={listAmounts[item.token_class_id] || ''}
+                                        onChange={(e) => handleAmountChange(item.token_class_id, e.target.value)}
                                         placeholder="Amount"
+                                        className="w-24 p-2 border rounded focus:ring-indigo-500 focus:border-indigo-500"
+                                        disabled={listingClassId === item.token_class_id}
+                                    />
+                                    <input
+                                        type="text"
+                                        inputMode="decimal"
+                                        value={listPrices[item.token_class_id] || ''}
+                                        onChange={(e) => handlePriceChange(item.token_class_id, e.target.value)}
+                                        placeholder={item.metadata ? `${utils.format.formatNearAmount(item.metadata.price_per_copy, 4)} NEAR` : 'NEAR each'}
                                         className="flex-grow p-2 border rounded focus:ring-indigo-500 focus:border-indigo-500"
-                                        disabled={approvingClassId === item.token_class_id}
+                                        disabled={listingClassId === item.token_class_id}
                                     />
                                     <button
-                                        onClick={() => handleApprove(item.token_class_id)}
+                                        onClick={() => handleList(item.token_class_id)}
                                         className="px-4 py-2 bg-blue-600 text-white rounded hover:bg-blue-700 disabled:opacity-50"
-                                        disabled={approvingClassId === item.token_class_id || !approvalAmounts[item.token_class_id]}
+                                        disabled={listingClassId === item.token_class_id || !listAmounts[item.token_class_id]}
                                     >
-                                        {approvingClassId === item.token_class_id ? 'Approving...' : 'Approve'}
+                                        {listingClassId === item.token_class_id ? 'Listing...' : 'List'}
                                     </button>
                                 </div>
                             </div>
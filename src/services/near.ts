@@ -277,41 +277,6 @@ export const getAllSftMetadata = async (near: Near): Promise<TokenClassMetadata[
     }
 };
 
-export const getAllOwners = async (near: Near): Promise<string[]> => {
-    try {
-        if (!near) {
-            throw new Error("NEAR connection object is required for view calls.");
-        }
-
-        const viewArgs = {
-            // Consider adding pagination args here if needed
-            // from_index: "0", 
-            // limit: 50 
-        };
-
-        const response = await near.connection.provider.query<CodeResult>({
-            request_type: "call_function",
-            finality: "optimistic",
-            account_id: CONTRACT_NAME,
-            method_name: "sft_get_owners", 
-            args_base64: btoa(JSON.stringify(viewArgs))
-        });
-
-        if (response && response.result && response.result.length > 0) { 
-            const resultData = JSON.parse(Buffer.from(response.result).toString());
-
-            return resultData as string[];
-        } else {
-            console.warn("Received no result from sft_get_owners view call");
-            return [];
-        }
-
-    } catch (error) {
-        console.error('Error fetching all onwers:', error);
-        return [];
-    }
-};
-
 // Add the JsonToken type to match the contract's return type for get_all_tokens
 // export interface JsonToken {
 //     token_id: string;
@@ -369,8 +334,25 @@ export const getAllOwners = async (near: Near): Promise<string[]> => {
 
 // --- New Service Functions for Marketplace --- 
 
+// Page size for views that take from_index/limit
+const VIEW_PAGE_SIZE = 50;
+
+// Extra deposit attached to marketplace calls for the caller's storage; the contract keeps what is
+// unused as the caller's storage balance
+const MARKET_STORAGE_DEPOSIT = utils.format.parseNearAmount("0.01") ?? "10000000000000000000000";
+
+// Matches the contract's Listing struct
+export interface Listing {
+    seller_id: string;
+    token_class_id: string;
+    price_per_copy: string; // yoctoNEAR, or the token's smallest unit when ft_token_id is set
+    quantity: string;
+    expires_at: string | null; // Nanoseconds since epoch
+    ft_token_id: string | null;
+}
+
 /**
- * Fetches the SFT inventory for a given account ID.
+ * Fetches the SFT inventory for a given account ID, page by page.
  * Returns a map where keys are token_class_id and values are balances (U64 string).
  */
 export const sftInventoryOfOwner = async (
@@ -381,23 +363,28 @@ export const sftInventoryOfOwner = async (
         if (!near) throw new Error("NEAR connection object is required.");
         if (!accountId) throw new Error("Account ID is required.");
 
-        // Use generic <CodeResult> for provider.query
-        const response = await near.connection.provider.query<CodeResult>({
-            request_type: "call_function",
-            finality: "optimistic",
-            account_id: CONTRACT_NAME,
-            method_name: "sft_inventory_of_owner",
-            args_base64: btoa(JSON.stringify({ account_id: accountId }))
-        });
+        const inventory: Record<string, string> = {};
+        for (let fromIndex = 0; ; fromIndex += VIEW_PAGE_SIZE) {
+            // Use generic <CodeResult> for provider.query
+            const response = await near.connection.provider.query<CodeResult>({
+                request_type: "call_function",
+                finality: "optimistic",
+                account_id: CONTRACT_NAME,
+                method_name: "sft_inventory_of_owner",
+                args_base64: btoa(JSON.stringify({ account_id: accountId, from_index: fromIndex, limit: VIEW_PAGE_SIZE }))
+            });
 
-        // Check response.result exists and has length
-        if (response && response.result && response.result.length > 0) {
-            const resultData = JSON.parse(Buffer.from(response.result).toString());
-            return resultData as Record<string, string>; 
-        } else {
-            console.warn(`Received no result from sft_inventory_of_owner for ${accountId}`);
-            return {};
+            // Check response.result exists and has length
+            if (!response || !response.result || response.result.length === 0) {
+                console.warn(`Received no result from sft_inventory_of_owner for ${accountId}`);
+                break;
+            }
+            const page = JSON.parse(Buffer.from(response.result).toString()) as Record<string, string>;
+            Object.assign(inventory, page);
+            // A short page is the last one
+            if (Object.keys(page).length < VIEW_PAGE_SIZE) break;
         }
+        return inventory;
     } catch (error) {
         console.error(`Error fetching inventory for ${accountId}:`, error);
         return {};
@@ -405,12 +392,14 @@ export const sftInventoryOfOwner = async (
 };
 
 /**
- * Approves the marketplace contract to transfer SFTs on behalf of the user.
+ * Lists copies of an SFT for sale on the marketplace at a price per copy (yoctoNEAR).
+ * Listing again replaces the seller's existing listing for the class.
  */
-export const sftApproveMarketplace = async (
+export const marketListSft = async (
     wallet: Wallet,
     token_class_id: string,
-    amount: string
+    price_per_copy: string,
+    quantity: string
 ): Promise<any> => {
     try {
         if (!wallet) throw new Error("Wallet connection is required.");
@@ -421,12 +410,12 @@ export const sftApproveMarketplace = async (
         const accountId = accounts[0].accountId;
 
         const callArgs = {
-            account_id_to_approve: CONTRACT_NAME,
             token_class_id,
-            amount 
+            price_per_copy,
+            quantity
         };
 
-        console.log("Calling sft_approve with args:", JSON.stringify(callArgs, null, 2));
+        console.log("Calling market_list_sft with args:", JSON.stringify(callArgs, null, 2));
 
         const result = await wallet.signAndSendTransaction({
             signerId: accountId,
@@ -435,10 +424,10 @@ export const sftApproveMarketplace = async (
                 {
                     type: 'FunctionCall',
                     params: {
-                        methodName: 'sft_approve',
+                        methodName: 'market_list_sft',
                         args: callArgs, 
                         gas: '100000000000000',
-                        deposit: utils.format.parseNearAmount("0.01") ?? "10000000000000000000000"
+                        deposit: MARKET_STORAGE_DEPOSIT
                     }
                 }
             ]
@@ -447,61 +436,55 @@ export const sftApproveMarketplace = async (
         return { success: true, result };
 
     } catch (error) {
-        console.error("Error in sftApproveMarketplace:", error);
+        console.error("Error in marketListSft:", error);
         throw error;
     }
 };
 
 /**
- * Gets accounts that have approved the marketplace for a specific token class.
- * Note: Requires passing potential seller IDs due to contract limitations.
- * Returns a map where keys are seller Account IDs and values are approved amounts (U64 string).
+ * Fetches every marketplace listing for a token class, page by page.
+ * Expired listings are included; callers filter on expires_at.
  */
-export const getMarketApprovedSellers = async (
+export const getListingsForClass = async (
     near: Near, // NEAR connection object
-    token_class_id: string,
-    seller_ids: string[] // Array of potential seller account IDs to check
-): Promise<Record<string, string>> => {
+    token_class_id: string
+): Promise<Listing[]> => {
      try {
         if (!near) throw new Error("NEAR connection object is required.");
-        if (!seller_ids || seller_ids.length === 0) {
-            // Return empty if no sellers provided to check
-            return {};
-        }
-
-        const viewArgs = {
-            token_class_id,
-            seller_ids
-        };
 
-        // Use generic <CodeResult> for provider.query
-        const response = await near.connection.provider.query<CodeResult>({
-            request_type: "call_function",
-            finality: "optimistic",
-            account_id: CONTRACT_NAME,
-            method_name: "get_market_approved_sellers",
-            args_base64: btoa(JSON.stringify(viewArgs))
-        });
-
-        // Check response.result exists and has length
-        if (response && response.result && response.result.length > 0) {
-            const resultData = JSON.parse(Buffer.from(response.result).toString());
+        const listings: Listing[] = [];
+        for (let fromIndex = 0; ; fromIndex += VIEW_PAGE_SIZE) {
+            // Use generic <CodeResult> for provider.query
+            const response = await near.connection.provider.query<CodeResult>({
+                request_type: "call_function",
+                finality: "optimistic",
+                account_id: CONTRACT_NAME,
+                method_name: "market_listings_for_class",
+                args_base64: btoa(JSON.stringify({ token_class_id, from_index: fromIndex, limit: VIEW_PAGE_SIZE }))
+            });
 
-            return resultData as Record<string, string>; 
-        } else {
-            console.warn(`Received no result from get_market_approved_sellers for ${token_class_id}`);
-            return {};
+            // Check response.result exists and has length
+            if (!response || !response.result || response.result.length === 0) {
+                console.warn(`Received no result from market_listings_for_class for ${token_class_id}`);
+                break;
+            }
+            const page = JSON.parse(Buffer.from(response.result).toString()) as Listing[];
+            listings.push(...page);
+            // A short page is the last one
+            if (page.length < VIEW_PAGE_SIZE) break;
         }
+        return listings;
 
     } catch (error) {
-        console.error(`Error fetching market approved sellers for ${token_class_id}:`, error);
-        return {};
+        console.error(`Error fetching listings for ${token_class_id}:`, error);
+        return [];
     }
 };
 
 
 /**
- * Buys 1 copy of an SFT from a specific seller via the marketplace.
+ * Buys 1 copy of an SFT from a seller's listing. `price` is the listing's price_per_copy;
+ * a storage deposit is attached on top of it for the buyer's new balance entry.
  */
 export const marketBuySft = async (
     wallet: Wallet,
@@ -525,7 +508,7 @@ export const marketBuySft = async (
         };
 
         console.log("Calling market_buy_sft with args:", JSON.stringify(callArgs, null, 2));
-        const deposit = price;
+        const deposit = (BigInt(price) + BigInt(MARKET_STORAGE_DEPOSIT)).toString();
         dispatch(changeAlertContent(token_class_id));
         const result = await wallet.signAndSendTransaction({
             signerId: accountId,